@@ -0,0 +1,156 @@
+//! Synthetic bencode corpora for the benchmarks.
+//!
+//! Everything is generated deterministically so that no files or network
+//! access are needed and runs are comparable.
+
+#![allow(dead_code)]
+
+use ben::encode::Dict;
+use ben::Encoder;
+
+/// Deterministic pseudo random bytes.
+pub fn bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed | 1;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+/// A KRPC `ping` query.
+pub fn krpc_ping() -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut dict = buf.add_dict();
+    let mut args = dict.add_dict("a");
+    args.add("id", &bytes(20, 1)[..]);
+    args.finish();
+    dict.add("q", "ping");
+    dict.add("t", "aa");
+    dict.add("y", "q");
+    dict.finish();
+    buf
+}
+
+/// A KRPC `find_node` response with 8 compact nodes.
+pub fn krpc_find_node() -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut dict = buf.add_dict();
+    let mut resp = dict.add_dict("r");
+    resp.add("id", &bytes(20, 2)[..]);
+    resp.add("nodes", &bytes(8 * 26, 3)[..]);
+    resp.finish();
+    dict.add("t", "aa");
+    dict.add("y", "r");
+    dict.finish();
+    buf
+}
+
+/// A KRPC `get_peers` response with `peers` compact peer addresses.
+pub fn krpc_get_peers(peers: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut dict = buf.add_dict();
+    let mut resp = dict.add_dict("r");
+    resp.add("id", &bytes(20, 4)[..]);
+    resp.add("token", &bytes(8, 5)[..]);
+    let mut values = resp.add_list("values");
+    for i in 0..peers {
+        values.add(&bytes(6, i as u64)[..]);
+    }
+    values.finish();
+    resp.finish();
+    dict.add("t", "aa");
+    dict.add("y", "r");
+    dict.finish();
+    buf
+}
+
+/// A torrent with `files` files and `pieces` piece hashes. A single file
+/// torrent if `files` is 1.
+pub fn metainfo(files: usize, pieces: usize) -> Vec<u8> {
+    let piece_length = 262_144_i64;
+    let total = pieces as i64 * piece_length;
+
+    let mut buf = Vec::new();
+    let mut dict = buf.add_dict();
+    dict.add("announce", "http://tracker.example.com:6969/announce");
+    let mut tiers = dict.add_list("announce-list");
+    for i in 0..3 {
+        let mut tier = tiers.add_list();
+        tier.add(&*format!("udp://tracker{}.example.com:1337/announce", i));
+        tier.finish();
+    }
+    tiers.finish();
+    dict.add("comment", "Synthetic benchmark torrent");
+    dict.add("created by", "ben");
+    dict.add("creation date", 1_600_000_000_i64);
+
+    let mut info = dict.add_dict("info");
+    if files == 1 {
+        info.add("length", total);
+    } else {
+        add_files(&mut info, files, total);
+    }
+    info.add("name", "example");
+    info.add("piece length", piece_length);
+    info.add("pieces", &bytes(pieces * 20, 6)[..]);
+    info.finish();
+    dict.finish();
+    buf
+}
+
+fn add_files(info: &mut Dict<'_, Vec<u8>>, files: usize, total: i64) {
+    let mut list = info.add_list("files");
+    let mut left = total;
+    for i in 0..files {
+        let len = if i + 1 == files {
+            left
+        } else {
+            total / files as i64
+        };
+        left -= len;
+
+        let mut file = list.add_dict();
+        file.add("length", len);
+        let mut path = file.add_list("path");
+        path.add(&*format!("dir{}", i % 16));
+        path.add(&*format!("file{:05}.bin", i));
+        path.finish();
+        file.finish();
+    }
+    list.finish();
+}
+
+/// A value nested `depth` levels deep, alternating lists and dicts.
+pub fn nested(depth: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for i in 0..depth {
+        if i % 2 == 0 {
+            buf.push(b'l');
+        } else {
+            buf.extend_from_slice(b"d1:k");
+        }
+    }
+    buf.extend_from_slice(b"i42e");
+    buf.resize(buf.len() + depth, b'e');
+    buf
+}
+
+/// Key `i` of `wide_dict`.
+pub fn key(i: usize) -> String {
+    format!("key{:06}", i)
+}
+
+/// A dict with `len` sorted keys and integer values.
+pub fn wide_dict(len: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut dict = buf.add_dict();
+    for i in 0..len {
+        dict.add(key(i), i as i64);
+    }
+    dict.finish();
+    buf
+}
@@ -0,0 +1,97 @@
+//! Encoding benchmarks.
+//!
+//! Run with `cargo bench --bench encode`.
+
+mod corpus;
+
+use ben::{Decoder, Encode, Encoder, Parser};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+fn dict(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_dict");
+    for &len in &[10, 100, 1_000] {
+        let keys: Vec<_> = (0..len).map(corpus::key).collect();
+        group.throughput(Throughput::Bytes(corpus::wide_dict(len).len() as u64));
+
+        let mut buf = Vec::new();
+        group.bench_with_input(BenchmarkId::new("dict", len), &keys, |b, keys| {
+            b.iter(|| {
+                buf.clear();
+                let mut dict = buf.add_dict();
+                for (i, key) in keys.iter().enumerate() {
+                    dict.add(key, i as i64);
+                }
+                dict.finish();
+                black_box(buf.len());
+            })
+        });
+
+        // Keys added in reverse so that the dict has to sort them
+        group.bench_with_input(BenchmarkId::new("ordered_dict", len), &keys, |b, keys| {
+            b.iter(|| {
+                buf.clear();
+                let mut dict = buf.add_ordered_dict();
+                for (i, key) in keys.iter().enumerate().rev() {
+                    dict.add(key, i as i64);
+                }
+                dict.finish();
+                black_box(buf.len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn krpc(c: &mut Criterion) {
+    let id = corpus::bytes(20, 1);
+    let token = corpus::bytes(8, 2);
+    let peers: Vec<_> = (0..50).map(|i| corpus::bytes(6, i)).collect();
+
+    let mut group = c.benchmark_group("encode_krpc");
+    let mut buf = Vec::new();
+    group.bench_function("get_peers_50", |b| {
+        b.iter(|| {
+            buf.clear();
+            let mut dict = buf.add_dict();
+            let mut resp = dict.add_dict("r");
+            resp.add("id", &id[..]);
+            resp.add("token", &token[..]);
+            let mut values = resp.add_list("values");
+            for peer in &peers {
+                values.add(&peer[..]);
+            }
+            values.finish();
+            resp.finish();
+            dict.add("t", "aa");
+            dict.add("y", "r");
+            dict.finish();
+            black_box(buf.len());
+        })
+    });
+    group.finish();
+}
+
+fn reencode(c: &mut Criterion) {
+    let src = corpus::metainfo(100, 10_000);
+    let mut parser = Parser::new();
+    let decoder = parser.parse::<Decoder>(&src).unwrap();
+
+    let mut group = c.benchmark_group("encode_metainfo");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    let mut buf = Vec::new();
+    group.bench_function("reencode", |b| {
+        b.iter(|| {
+            buf.clear();
+            decoder.encode(&mut buf);
+            black_box(buf.len());
+        })
+    });
+    group.bench_function("encoded_len", |b| {
+        b.iter(|| black_box(decoder.encoded_len()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, dict, krpc, reencode);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+//! Parsing and lookup benchmarks.
+//!
+//! Run with `cargo bench --bench parse`.
+
+mod corpus;
+
+use ben::decode::Dict;
+use ben::{Decoder, Parser};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+fn bench_parse(c: &mut Criterion, name: &str, inputs: &[(String, Vec<u8>)]) {
+    let mut group = c.benchmark_group(name);
+    let mut parser = Parser::new();
+    for (id, buf) in inputs {
+        group.throughput(Throughput::Bytes(buf.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(id), buf, |b, buf| {
+            b.iter(|| {
+                let d = parser.parse::<Decoder>(black_box(buf)).unwrap();
+                black_box(d.as_raw_bytes().len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn krpc(c: &mut Criterion) {
+    bench_parse(
+        c,
+        "parse_krpc",
+        &[
+            ("ping".into(), corpus::krpc_ping()),
+            ("find_node".into(), corpus::krpc_find_node()),
+            ("get_peers_50".into(), corpus::krpc_get_peers(50)),
+        ],
+    );
+}
+
+fn metainfo(c: &mut Criterion) {
+    let inputs: Vec<_> = [(1, 1_000), (100, 10_000), (10_000, 100_000)]
+        .iter()
+        .map(|&(files, pieces)| {
+            let id = format!("{}_files_{}_pieces", files, pieces);
+            (id, corpus::metainfo(files, pieces))
+        })
+        .collect();
+    bench_parse(c, "parse_metainfo", &inputs);
+}
+
+fn nested(c: &mut Criterion) {
+    let inputs: Vec<_> = [10, 100, 1_000]
+        .iter()
+        .map(|&depth| (depth.to_string(), corpus::nested(depth)))
+        .collect();
+    bench_parse(c, "parse_nested", &inputs);
+}
+
+fn dict_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dict_get");
+    for &len in &[10, 100, 1_000] {
+        let buf = corpus::wide_dict(len);
+        let mut parser = Parser::new();
+        let dict = parser.parse::<Dict>(&buf).unwrap();
+        for (name, key) in [
+            ("first", corpus::key(0)),
+            ("last", corpus::key(len - 1)),
+            ("missing", "zzz".into()),
+        ] {
+            let id = BenchmarkId::new(name, len);
+            group.bench_with_input(id, &key, |b, key| {
+                b.iter(|| black_box(dict.get_int(black_box(key))))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, krpc, metainfo, nested, dict_get);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+//! Throughput of the integer and string length scanning in the parser.
+//!
+//! Run with `cargo bench --bench scan`.
+
+mod corpus;
+
+use ben::{Decoder, Encoder, Parser};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+/// A list of 1000 integers with `digits` digits each.
+fn ints(digits: usize) -> Vec<u8> {
+    let low = 10_i64.pow(digits as u32 - 1);
+    let mut buf = Vec::new();
+    let mut list = buf.add_list();
+    for i in 0..1000 {
+        list.add(low + i * 7919 % low);
+    }
+    list.finish();
+    buf
+}
+
+/// A list of `n` short strings, as in dictionary keys and KRPC messages.
+fn strings(n: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut list = buf.add_list();
+    for i in 0..n {
+        list.add(&"abcdefghijklmnopqrstuvwxyz"[..i % 26]);
+    }
+    list.finish();
+    buf
+}
+
+fn bench(c: &mut Criterion, name: &str, inputs: &[(usize, Vec<u8>)]) {
+    let mut group = c.benchmark_group(name);
+    let mut parser = Parser::new();
+    for (n, buf) in inputs {
+        group.throughput(Throughput::Bytes(buf.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(n), buf, |b, buf| {
+            b.iter(|| {
+                let d = parser.parse::<Decoder>(black_box(buf)).unwrap();
+                black_box(d.as_raw_bytes().len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn scan(c: &mut Criterion) {
+    let inputs = |f: fn(usize) -> Vec<u8>, params: &[usize]| -> Vec<_> {
+        params.iter().map(|&n| (n, f(n))).collect()
+    };
+    let metainfo = |pieces| corpus::metainfo(1, pieces);
+    bench(c, "metainfo_pieces", &inputs(metainfo, &[1_000, 100_000]));
+    bench(c, "ints", &inputs(ints, &[3, 10, 18]));
+    bench(c, "strings", &inputs(strings, &[1_000, 100_000]));
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Parse arbitrary input and check the resulting tokens against the buffer.
+
+#![no_main]
+
+use ben::{tape, Decoder, Parser};
+use libfuzzer_sys::fuzz_target;
+
+/// The end of the value in the buffer. Integer tokens exclude the closing
+/// 'e', string tokens end with the string.
+fn value_end(decoder: &Decoder<'_, '_>) -> usize {
+    decoder.tape()[0].range().end + usize::from(decoder.is_int())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.depth_limit(256);
+    let decoder = match parser.parse::<Decoder>(data) {
+        Ok(decoder) => decoder,
+        Err(_) => return,
+    };
+
+    // The whole input is consumed by the root value. Scalars exclude their
+    // delimiters from the raw bytes, so only the end can be compared.
+    assert_eq!(data.len(), value_end(&decoder));
+    if decoder.is_list() || decoder.is_dict() {
+        assert_eq!(data, decoder.as_raw_bytes());
+    }
+
+    // `Tape::load` validates every token against the buffer, so this
+    // catches tokens that `as_raw_bytes` would read out of bounds
+    let saved = tape::save(&decoder);
+    let loaded = tape::Tape::load(&saved, data).expect("parsed tape is invalid");
+    assert_eq!(decoder.tape(), loaded.tokens());
+
+    let tape = decoder.tape();
+    for i in 0..tape.len() {
+        let value = decoder.at(i).unwrap();
+        let raw = value.as_raw_bytes();
+        if value.is_int() {
+            let _ = value.as_int();
+        } else if value.is_bytes() {
+            assert_eq!(Some(raw), value.as_bytes());
+        }
+    }
+});
@@ -0,0 +1,56 @@
+//! Parse a prefix of arbitrary input and check that all parsers agree.
+
+#![no_main]
+
+use ben::{Decoder, FixedParser, Parser, SliceParser, Token};
+use libfuzzer_sys::fuzz_target;
+
+/// The end of the value in the buffer. Integer tokens exclude the closing
+/// 'e', string tokens end with the string.
+fn value_end(decoder: &Decoder<'_, '_>) -> usize {
+    decoder.tape()[0].range().end + usize::from(decoder.is_int())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.depth_limit(256);
+    let result = parser
+        .parse_prefix::<Decoder>(data)
+        .map(|(d, pos)| (value_end(&d), pos));
+
+    let mut fixed = FixedParser::<64>::new();
+    fixed.depth_limit(256);
+    let fixed_result = fixed
+        .parse_prefix::<Decoder>(data)
+        .map(|(d, pos)| (value_end(&d), pos));
+
+    let mut tokens = [Token::default(); 64];
+    let mut slice = SliceParser::new(&mut tokens);
+    slice.depth_limit(256);
+    let slice_result = slice
+        .parse_prefix::<Decoder>(data)
+        .map(|(d, pos)| (value_end(&d), pos));
+    assert_eq!(fixed_result, slice_result);
+
+    let (end, pos) = match result {
+        Ok(res) => res,
+        Err(err) => {
+            // The fixed parsers may only differ by running out of tokens
+            if !matches!(fixed_result, Err(ben::Error::TokenLimit { .. })) {
+                assert_eq!(Err(err), fixed_result);
+            }
+            return;
+        }
+    };
+    assert_eq!(end, pos);
+    assert!(pos <= data.len());
+    if let Ok(fixed_result) = fixed_result {
+        assert_eq!((end, pos), fixed_result);
+        assert_eq!(parser.tokens(), fixed.tokens());
+    }
+
+    // The prefix on its own parses to the same tokens
+    let tokens = parser.tokens().to_vec();
+    parser.parse::<Decoder>(&data[..pos]).unwrap();
+    assert_eq!(tokens, parser.tokens());
+});
@@ -0,0 +1,79 @@
+//! Differential check that every accepted canonical input re-encodes
+//! identically through `Value`, and that any accepted input normalizes to a
+//! fixed point.
+
+#![no_main]
+
+use ben::value::Value;
+use ben::{Decode, Decoder, Encode, Parser};
+use libfuzzer_sys::fuzz_target;
+
+/// Whether `decoder`, encoded at `data[pos..]`, is canonical bencode:
+/// integers and string lengths without leading zeros, no negative zero, and
+/// dictionary keys strictly increasing. Returns the end of the value if so.
+fn canonical_end(data: &[u8], decoder: &Decoder<'_, '_>, pos: usize) -> Option<usize> {
+    let range = decoder.tape()[0].range();
+    if let Some(list) = decoder.as_list() {
+        let mut pos = pos + 1;
+        for v in list.iter() {
+            pos = canonical_end(data, &v, pos)?;
+        }
+        Some(pos + 1)
+    } else if let Some(dict) = decoder.as_dict() {
+        let mut pos = pos + 1;
+        let mut prev: Option<&[u8]> = None;
+        for (k, v) in dict.iter() {
+            let key = k.as_raw_bytes();
+            if prev.is_some_and(|prev| prev >= key) {
+                return None;
+            }
+            prev = Some(key);
+            pos = canonical_end(data, &k, pos)?;
+            pos = canonical_end(data, &v, pos)?;
+        }
+        Some(pos + 1)
+    } else if decoder.is_int() {
+        let digits = decoder.as_raw_bytes();
+        let negative = digits.starts_with(b"-");
+        let digits = &digits[usize::from(negative)..];
+        let canonical = if digits == b"0" {
+            !negative
+        } else {
+            digits[0] != b'0'
+        };
+        canonical.then_some(range.end + 1)
+    } else {
+        // The length prefix runs from `pos` up to the ':'
+        let digits = &data[pos..range.start - 1];
+        (digits == b"0" || digits[0] != b'0').then_some(range.end)
+    }
+}
+
+fn is_canonical(data: &[u8], decoder: &Decoder<'_, '_>) -> bool {
+    canonical_end(data, decoder, 0).is_some()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.depth_limit(256);
+    let decoder = match parser.parse::<Decoder>(data) {
+        Ok(decoder) => decoder,
+        Err(_) => return,
+    };
+
+    // `Encode for Decoder` copies source bytes, so compare against the
+    // independent `Value` encoder instead
+    let canonical = is_canonical(data, &decoder);
+    let value = Value::decode(decoder).unwrap();
+    let normalized = value.encode_to_vec();
+    if canonical {
+        assert_eq!(data, &normalized[..]);
+    }
+
+    let mut parser = Parser::new();
+    let decoder = parser.parse::<Decoder>(&normalized).unwrap();
+    assert!(is_canonical(&normalized, &decoder));
+    let reparsed = Value::decode(decoder).unwrap();
+    assert_eq!(value, reparsed);
+    assert_eq!(normalized, reparsed.encode_to_vec());
+});
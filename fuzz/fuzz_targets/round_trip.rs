@@ -0,0 +1,17 @@
+//! Encode arbitrary valid values, parse them back and re-encode them.
+
+#![no_main]
+
+use ben::value::Value;
+use ben::{Decoder, Encode, Parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|value: Value| {
+    let buf = value.encode_to_vec();
+    assert_eq!(buf.len(), value.encoded_len());
+
+    let mut parser = Parser::new();
+    let decoder = parser.parse::<Decoder>(&buf).unwrap();
+    assert_eq!(buf, decoder.encode_to_vec());
+    assert_eq!(value, parser.parse::<Value>(&buf).unwrap());
+});
@@ -0,0 +1,655 @@
+//! Creation of metainfo (`.torrent`) files from files on disk.
+
+use crate::encode::{Dict, Encode, Encoder, HashSink, Sink};
+use crate::merkle::{self, Hash};
+use crate::metainfo::BLOCK_SIZE;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Largest piece length chosen automatically.
+const MAX_AUTO_PIECE_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// Number of pieces the automatic piece length aims to stay below.
+const TARGET_PIECES: u64 = 2048;
+
+/// Builds a torrent from a file or a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ben::builder::TorrentBuilder;
+/// use ben::Encode;
+///
+/// let mut builder = TorrentBuilder::new("artefacts");
+/// builder.announce_tier(vec!["http://tracker.example/announce".into()]);
+/// builder.private(true);
+/// let torrent = builder.build().unwrap();
+/// std::fs::write("artefacts.torrent", torrent.encode_to_vec()).unwrap();
+/// ```
+pub struct TorrentBuilder {
+    path: PathBuf,
+    piece_length: Option<u64>,
+    v2: bool,
+    trackers: Vec<Vec<String>>,
+    web_seeds: Vec<String>,
+    private: bool,
+    comment: Option<String>,
+}
+
+impl TorrentBuilder {
+    /// Create a new builder for the file or directory at given path.
+    ///
+    /// Symlinks to files inside a directory are followed, symlinks to
+    /// directories are skipped so link cycles can't recurse forever.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            piece_length: None,
+            v2: false,
+            trackers: vec![],
+            web_seeds: vec![],
+            private: false,
+            comment: None,
+        }
+    }
+
+    /// Set the piece length. It must be a power of two of at least 16 KiB.
+    ///
+    /// If not set, a piece length is chosen based on the total size.
+    pub fn piece_length(&mut self, piece_length: u64) {
+        self.piece_length = Some(piece_length);
+    }
+
+    /// Also describe the content in the v2 format (BEP 52), creating a
+    /// hybrid torrent.
+    pub fn v2(&mut self, v2: bool) {
+        self.v2 = v2;
+    }
+
+    /// Add a tier of tracker URLs. Tiers are tried in the order they are added.
+    pub fn announce_tier(&mut self, tier: Vec<String>) {
+        if !tier.is_empty() {
+            self.trackers.push(tier);
+        }
+    }
+
+    /// Add a web seed URL (BEP 19).
+    pub fn web_seed<S: Into<String>>(&mut self, url: S) {
+        self.web_seeds.push(url.into());
+    }
+
+    /// Set the private flag (BEP 27).
+    pub fn private(&mut self, private: bool) {
+        self.private = private;
+    }
+
+    /// Set a free-form comment.
+    pub fn comment<S: Into<String>>(&mut self, comment: S) {
+        self.comment = Some(comment.into());
+    }
+
+    /// Read and hash all files, returning the torrent.
+    pub fn build(&self) -> io::Result<Torrent> {
+        let name = match self.path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_owned(),
+            None => return Err(invalid_input("path must end in a UTF-8 file name")),
+        };
+
+        let single_file = fs::metadata(&self.path)?.is_file();
+        let mut sources = vec![];
+        if single_file {
+            sources.push((vec![], self.path.clone()));
+        } else {
+            walk(&self.path, &mut vec![], &mut sources)?;
+            if sources.is_empty() {
+                return Err(invalid_input("directory contains no files"));
+            }
+        }
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let piece_length = match self.piece_length {
+            Some(len) if len >= BLOCK_SIZE as u64 && len.is_power_of_two() => len,
+            Some(_) => {
+                return Err(invalid_input(
+                    "piece length must be a power of two of at least 16 KiB",
+                ))
+            }
+            None => {
+                let mut total = 0;
+                for (_, path) in &sources {
+                    total += fs::metadata(path)?.len();
+                }
+                auto_piece_length(total)
+            }
+        };
+
+        let mut hasher = PieceHasher::new(piece_length);
+        let mut files = Vec::with_capacity(sources.len());
+        let last = sources.len() - 1;
+        for (i, (path, source)) in sources.into_iter().enumerate() {
+            let mut reader = HashingReader {
+                inner: File::open(&source)?,
+                hasher: &mut hasher,
+            };
+
+            let (length, pieces_root, piece_layer) = if self.v2 {
+                let hashes = merkle::hash_file(&mut reader, piece_length)?;
+                (hashes.length, hashes.pieces_root, hashes.piece_layer)
+            } else {
+                let length = io::copy(&mut reader, &mut io::sink())?;
+                (length, None, vec![])
+            };
+
+            // Hybrid torrents align every file to a piece boundary so the v1
+            // pieces line up with the v2 piece layers.
+            let mut padding = 0;
+            if self.v2 && i != last && length % piece_length != 0 {
+                padding = piece_length - length % piece_length;
+                hasher.pad(padding);
+            }
+
+            files.push(TorrentFile {
+                path,
+                length,
+                padding,
+                pieces_root,
+                piece_layer,
+            });
+        }
+
+        Ok(Torrent {
+            name,
+            single_file,
+            piece_length,
+            pieces: hasher.finish(),
+            files,
+            v2: self.v2,
+            trackers: self.trackers.clone(),
+            web_seeds: self.web_seeds.clone(),
+            private: self.private,
+            comment: self.comment.clone(),
+        })
+    }
+}
+
+/// A torrent created by `TorrentBuilder`.
+///
+/// Encoding it produces the canonical bencoded metainfo file.
+#[derive(Debug, Clone)]
+pub struct Torrent {
+    name: String,
+    single_file: bool,
+    piece_length: u64,
+    pieces: Vec<u8>,
+    files: Vec<TorrentFile>,
+    v2: bool,
+    trackers: Vec<Vec<String>>,
+    web_seeds: Vec<String>,
+    private: bool,
+    comment: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TorrentFile {
+    path: Vec<String>,
+    length: u64,
+    padding: u64,
+    pieces_root: Option<Hash>,
+    piece_layer: Vec<u8>,
+}
+
+impl Torrent {
+    /// Returns the torrent name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the piece length.
+    pub fn piece_length(&self) -> u64 {
+        self.piece_length
+    }
+
+    /// Returns the SHA-1 hash of the bencoded `info` dictionary.
+    pub fn info_hash(&self) -> [u8; 20] {
+        HashSink::<Sha1>::hash(&InfoDict(self)).into()
+    }
+
+    /// Returns the SHA-256 hash of the bencoded `info` dictionary if this is a
+    /// v2 torrent.
+    pub fn info_hash_v2(&self) -> Option<[u8; 32]> {
+        if self.v2 {
+            Some(HashSink::<Sha256>::hash(&InfoDict(self)).into())
+        } else {
+            None
+        }
+    }
+
+    /// Encode this torrent into `sink` and return the v1 info-hash.
+    ///
+    /// The `info` dictionary is hashed while it is written, so this takes a
+    /// single pass over the piece hashes.
+    pub fn write_to<S: Sink>(&self, sink: &mut S) -> [u8; 20] {
+        self.encode_hashed(sink)
+    }
+
+    fn encode_hashed<E: Encoder>(&self, enc: &mut E) -> [u8; 20] {
+        let mut dict = enc.add_dict();
+
+        if let Some(url) = self.trackers.first().and_then(|tier| tier.first()) {
+            dict.add("announce", url);
+        }
+        if self.trackers.len() > 1 || self.trackers.iter().any(|tier| tier.len() > 1) {
+            dict.add("announce-list", &self.trackers);
+        }
+        if let Some(comment) = &self.comment {
+            dict.add("comment", comment);
+        }
+
+        let info_hash = dict.add_hashed::<_, _, Sha1>("info", InfoDict(self));
+
+        if self.v2 {
+            let mut layers = dict.add_dict("piece layers");
+            let mut files: Vec<_> = self
+                .files
+                .iter()
+                .filter(|f| !f.piece_layer.is_empty())
+                .collect();
+            files.sort_by_key(|f| f.pieces_root);
+            files.dedup_by_key(|f| f.pieces_root);
+            for file in files {
+                if let Some(root) = &file.pieces_root {
+                    layers.add(root, &file.piece_layer[..]);
+                }
+            }
+        }
+
+        if !self.web_seeds.is_empty() {
+            dict.add("url-list", &self.web_seeds);
+        }
+
+        info_hash.into()
+    }
+}
+
+impl Encode for Torrent {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        self.encode_hashed(enc);
+    }
+}
+
+struct InfoDict<'a>(&'a Torrent);
+
+impl Encode for InfoDict<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let t = self.0;
+        let mut info = enc.add_dict();
+
+        if t.v2 {
+            let mut tree = info.add_dict("file tree");
+            if t.single_file {
+                let file = &t.files[0];
+                let mut node = tree.add_dict(&t.name);
+                add_file_node(&mut node, file);
+            } else {
+                let files: Vec<_> = t.files.iter().map(|f| (&f.path[..], f)).collect();
+                add_file_tree(&mut tree, &files);
+            }
+        }
+
+        if t.single_file {
+            info.add("length", t.files[0].length as i64);
+        } else {
+            let mut list = info.add_list("files");
+            for file in &t.files {
+                {
+                    let mut entry = list.add_dict();
+                    entry.add("length", file.length as i64);
+                    entry.add("path", &file.path);
+                }
+                if file.padding > 0 {
+                    let mut entry = list.add_dict();
+                    entry.add("attr", "p");
+                    entry.add("length", file.padding as i64);
+                    let mut path = entry.add_list("path");
+                    path.add(".pad");
+                    path.add(file.padding.to_string());
+                }
+            }
+        }
+
+        if t.v2 {
+            info.add("meta version", 2);
+        }
+        info.add("name", &t.name);
+        info.add("piece length", t.piece_length as i64);
+        info.add("pieces", &t.pieces[..]);
+        if t.private {
+            info.add("private", 1);
+        }
+    }
+}
+
+/// Encodes files sorted by path into nested file tree dictionaries.
+fn add_file_tree<S: Sink>(dict: &mut Dict<'_, S>, files: &[(&[String], &TorrentFile)]) {
+    let mut i = 0;
+    while i < files.len() {
+        let name = &files[i].0[0];
+        let group = files[i..]
+            .iter()
+            .take_while(|(path, _)| &path[0] == name)
+            .count();
+
+        let mut node = dict.add_dict(name);
+        let (path, file) = files[i];
+        if path.len() == 1 {
+            add_file_node(&mut node, file);
+        } else {
+            let children: Vec<_> = files[i..i + group]
+                .iter()
+                .map(|(path, file)| (&path[1..], *file))
+                .collect();
+            add_file_tree(&mut node, &children);
+        }
+        i += group;
+    }
+}
+
+fn add_file_node<S: Sink>(node: &mut Dict<'_, S>, file: &TorrentFile) {
+    let mut props = node.add_dict("");
+    props.add("length", file.length as i64);
+    if let Some(root) = &file.pieces_root {
+        props.add("pieces root", root);
+    }
+}
+
+/// Collects all files below `dir` with their path components, skipping
+/// symlinked directories.
+fn walk(
+    dir: &Path,
+    prefix: &mut Vec<String>,
+    out: &mut Vec<(Vec<String>, PathBuf)>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => return Err(invalid_input("file names must be UTF-8")),
+        };
+
+        let path = entry.path();
+        let is_link = fs::symlink_metadata(&path)?.file_type().is_symlink();
+        let meta = fs::metadata(&path)?;
+        prefix.push(name);
+        if meta.is_dir() {
+            if !is_link {
+                walk(&path, prefix, out)?;
+            }
+        } else if meta.is_file() {
+            out.push((prefix.clone(), path));
+        }
+        prefix.pop();
+    }
+    Ok(())
+}
+
+fn auto_piece_length(total: u64) -> u64 {
+    let mut piece_length = BLOCK_SIZE as u64;
+    while total / piece_length > TARGET_PIECES && piece_length < MAX_AUTO_PIECE_LENGTH {
+        piece_length *= 2;
+    }
+    piece_length
+}
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Computes the v1 SHA-1 piece hashes over the concatenated file contents.
+struct PieceHasher {
+    piece_length: u64,
+    filled: u64,
+    hasher: Sha1,
+    pieces: Vec<u8>,
+}
+
+impl PieceHasher {
+    fn new(piece_length: u64) -> Self {
+        Self {
+            piece_length,
+            filled: 0,
+            hasher: Sha1::new(),
+            pieces: vec![],
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let n = (self.piece_length - self.filled).min(data.len() as u64) as usize;
+            self.hasher.update(&data[..n]);
+            self.filled += n as u64;
+            data = &data[n..];
+
+            if self.filled == self.piece_length {
+                self.pieces.extend(self.hasher.finalize_reset());
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Hashes `len` zero bytes of padding.
+    fn pad(&mut self, mut len: u64) {
+        let zeros = [0; BLOCK_SIZE];
+        while len > 0 {
+            let n = len.min(zeros.len() as u64) as usize;
+            self.update(&zeros[..n]);
+            len -= n as u64;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.filled > 0 {
+            self.pieces.extend(self.hasher.finalize());
+        }
+        self.pieces
+    }
+}
+
+/// Feeds everything read through it into a `PieceHasher`.
+struct HashingReader<'h, R> {
+    inner: R,
+    hasher: &'h mut PieceHasher,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metainfo::Metainfo;
+    use crate::Parser;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("ben-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn single_file_v1() {
+        let dir = TempDir::new("single-file-v1");
+        let path = dir.0.join("a.bin");
+        fs::write(&path, vec![1; 40000]).unwrap();
+
+        let mut builder = TorrentBuilder::new(&path);
+        builder.piece_length(16384);
+        builder.announce_tier(vec!["http://a/announce".into()]);
+        builder.comment("hello");
+        let torrent = builder.build().unwrap();
+        let buf = torrent.encode_to_vec();
+
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        metainfo.validate().unwrap();
+
+        let dict = metainfo.as_dict();
+        assert_eq!(Some("http://a/announce"), dict.get_str("announce"));
+        assert!(dict.get("announce-list").is_none());
+        assert_eq!(Some("hello"), dict.get_str("comment"));
+
+        let info = metainfo.info().unwrap();
+        assert_eq!(Some("a.bin"), info.name());
+        assert_eq!(Some(40000), info.as_dict().get_int("length"));
+        assert!(!info.is_v2());
+
+        let pieces = info.pieces().unwrap();
+        let data = vec![1; 40000];
+        let expected: Vec<u8> = data.chunks(16384).flat_map(Sha1::digest).collect();
+        assert_eq!(&expected[..], pieces);
+
+        let info_hash: [u8; 20] = Sha1::digest(info.as_dict().as_raw_bytes()).into();
+        assert_eq!(info_hash, torrent.info_hash());
+
+        let mut streamed = vec![];
+        assert_eq!(info_hash, torrent.write_to(&mut streamed));
+        assert_eq!(buf, streamed);
+    }
+
+    #[test]
+    fn directory_hybrid() {
+        let dir = TempDir::new("directory-hybrid");
+        let root = dir.0.join("content");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("b.txt"), vec![2; 100]).unwrap();
+        fs::write(root.join("sub").join("a.bin"), vec![3; 50000]).unwrap();
+        fs::write(root.join("a.txt"), vec![]).unwrap();
+
+        let mut builder = TorrentBuilder::new(&root);
+        builder.piece_length(16384);
+        builder.v2(true);
+        builder.announce_tier(vec!["http://a".into(), "http://b".into()]);
+        builder.announce_tier(vec!["udp://c".into()]);
+        builder.web_seed("http://seed/");
+        builder.private(true);
+        let torrent = builder.build().unwrap();
+        let buf = torrent.encode_to_vec();
+
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        metainfo.validate().unwrap();
+
+        let dict = metainfo.as_dict();
+        assert_eq!(
+            3,
+            dict.get_list("announce-list")
+                .unwrap()
+                .iter()
+                .flat_map(|t| t.into_list().unwrap())
+                .count()
+        );
+        assert_eq!(
+            Some("http://seed/"),
+            dict.get_list("url-list").unwrap().get_str(0)
+        );
+
+        let info = metainfo.info().unwrap();
+        assert!(info.is_hybrid());
+        assert_eq!(Some(1), info.as_dict().get_int("private"));
+
+        let files = info
+            .file_tree()
+            .unwrap()
+            .iter()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path.join("/")).collect();
+        assert_eq!(vec!["a.txt", "b.txt", "sub/a.bin"], paths);
+
+        let layers = metainfo.piece_layers().unwrap();
+        for file in &files {
+            assert!(merkle::verify_file(&layers, file, 16384));
+        }
+
+        let v1 = info
+            .v1_files()
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(4, v1.len());
+        assert!(v1[2].is_padding());
+        assert_eq!(16384 - 100, v1[2].length);
+
+        let info_hash: [u8; 32] = Sha256::digest(info.as_dict().as_raw_bytes()).into();
+        assert_eq!(Some(info_hash), torrent.info_hash_v2());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("directory-symlinks");
+        let root = dir.0.join("content");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("a.txt"), vec![1; 10]).unwrap();
+        symlink(&root, root.join("sub").join("loop")).unwrap();
+        symlink(root.join("sub").join("a.txt"), root.join("b.txt")).unwrap();
+
+        let mut builder = TorrentBuilder::new(&root);
+        builder.piece_length(16384);
+        let torrent = builder.build().unwrap();
+        let buf = torrent.encode_to_vec();
+
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        let paths: Vec<_> = metainfo
+            .info()
+            .unwrap()
+            .v1_files()
+            .unwrap()
+            .map(|f| f.unwrap().path.join("/"))
+            .collect();
+        assert_eq!(vec!["b.txt", "sub/a.txt"], paths);
+    }
+
+    #[test]
+    fn invalid_piece_length() {
+        let dir = TempDir::new("invalid-piece-length");
+        let path = dir.0.join("a");
+        fs::write(&path, b"a").unwrap();
+
+        let mut builder = TorrentBuilder::new(&path);
+        builder.piece_length(10000);
+        assert_eq!(
+            io::ErrorKind::InvalidInput,
+            builder.build().unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn auto_piece_length() {
+        assert_eq!(16384, super::auto_piece_length(0));
+        assert_eq!(16384, super::auto_piece_length(2048 * 16384));
+        assert_eq!(32768, super::auto_piece_length(2048 * 16384 + 16384));
+        assert_eq!(MAX_AUTO_PIECE_LENGTH, super::auto_piece_length(u64::MAX));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::error::{Error, Result};
 use crate::token::{Token, TokenKind};
-use std::fmt;
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::fmt;
 
 /// Decode to given type using provided `Decoder` object
 pub trait Decode<'a, 'p>: Sized {
@@ -40,6 +42,7 @@ impl<'a, 'p> Decode<'a, 'p> for &'a [u8] {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a, 'p> Decode<'a, 'p> for Vec<u8> {
     fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
         match decoder.as_bytes() {
@@ -67,6 +70,7 @@ impl<'a, 'p> Decode<'a, 'p> for i64 {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a, 'p> Decode<'a, 'p> for String {
     fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
         match decoder.as_str() {
@@ -81,7 +85,8 @@ impl<'a, 'p> Decode<'a, 'p> for String {
 pub struct Decoder<'a, 'p> {
     pub(crate) buf: &'a [u8],
     pub(crate) token: &'p Token,
-    pub(crate) rest: &'p [Token],
+    /// `token` followed by the rest of the tape it was split from.
+    pub(crate) tokens: &'p [Token],
 }
 
 impl fmt::Debug for Decoder<'_, '_> {
@@ -100,11 +105,61 @@ impl fmt::Debug for Decoder<'_, '_> {
 
 impl<'a, 'p> Decoder<'a, 'p> {
     pub(crate) fn new(buf: &'a [u8], tokens: &'p [Token]) -> Option<Self> {
-        if let [token, rest @ ..] = tokens {
-            Some(Decoder { buf, token, rest })
-        } else {
-            None
-        }
+        let token = tokens.first()?;
+        Some(Decoder { buf, token, tokens })
+    }
+
+    /// Returns the position of this object in the source buffer.
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) fn pos(&self) -> usize {
+        self.token.start as usize
+    }
+
+    /// Returns the token of this value.
+    pub fn token(&self) -> &'p Token {
+        self.token
+    }
+
+    /// Returns the tokens of this value and all its descendants.
+    ///
+    /// For the root value returned by the parser this is the complete tape.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::{Decoder, Parser, TokenKind};
+    ///
+    /// let parser = &mut Parser::new();
+    /// let decoder = parser.parse::<Decoder>(b"d1:ali1ei2eee").unwrap();
+    /// let kinds: Vec<_> = decoder.tape().iter().map(|t| t.kind()).collect();
+    /// assert_eq!(
+    ///     vec![TokenKind::Dict, TokenKind::ByteStr, TokenKind::List, TokenKind::Int, TokenKind::Int],
+    ///     kinds
+    /// );
+    /// ```
+    pub fn tape(&self) -> &'p [Token] {
+        // The subtree of `token` is contained in `tokens` (ensured by parser)
+        let len = self.token.next as usize;
+        &self.tokens[..len]
+    }
+
+    /// Returns a decoder for the value at `index` of this value's tape.
+    ///
+    /// Index 0 is this value itself. Returns `None` if `index` is outside
+    /// of `tape()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::{Decoder, Parser};
+    ///
+    /// let parser = &mut Parser::new();
+    /// let decoder = parser.parse::<Decoder>(b"d1:ali1ei2eee").unwrap();
+    /// assert_eq!(Some(2), decoder.at(4).unwrap().as_int());
+    /// assert!(decoder.at(5).is_none());
+    /// ```
+    pub fn at(&self, index: usize) -> Option<Decoder<'a, 'p>> {
+        Decoder::new(self.buf, self.tape().get(index..)?)
     }
 
     /// Returns raw bytes of this decoder.
@@ -169,7 +224,7 @@ impl<'a, 'p> Decoder<'a, 'p> {
             Some(List {
                 buf: self.buf,
                 token: self.token,
-                rest: self.rest,
+                tokens: self.tokens,
             })
         } else {
             None
@@ -222,7 +277,7 @@ impl<'a, 'p> Decoder<'a, 'p> {
             Some(Dict {
                 buf: self.buf,
                 token: self.token,
-                rest: self.rest,
+                tokens: self.tokens,
             })
         } else {
             None
@@ -271,20 +326,22 @@ impl<'a, 'p> Decoder<'a, 'p> {
         if !self.is_int() {
             return None;
         }
-        let mut val = 0;
+        // Accumulate the magnitude unsigned so that `i64::MIN` fits
+        let mut val: u64 = 0;
         let mut negative = false;
         for &c in self.as_raw_bytes() {
             if c == b'-' {
                 negative = true;
             } else {
-                let digit = i64::from(c - b'0');
+                let digit = u64::from(c - b'0');
                 val = (val * 10) + digit;
             }
         }
         if negative {
-            val *= -1;
+            Some((val as i64).wrapping_neg())
+        } else {
+            Some(val as i64)
         }
-        Some(val)
     }
 
     /// Return this decoder as a byte slice.
@@ -325,7 +382,7 @@ impl<'a, 'p> Decoder<'a, 'p> {
     /// ```
     pub fn as_str(&self) -> Option<&'a str> {
         let bytes = self.as_bytes()?;
-        std::str::from_utf8(bytes).ok()
+        core::str::from_utf8(bytes).ok()
     }
 
     /// Return this decoder as a string slice.
@@ -365,7 +422,8 @@ impl<'a, 'p> Decoder<'a, 'p> {
 pub struct List<'a, 'p> {
     buf: &'a [u8],
     token: &'p Token,
-    rest: &'p [Token],
+    /// `token` followed by the rest of the tape.
+    tokens: &'p [Token],
 }
 
 impl fmt::Debug for List<'_, '_> {
@@ -388,7 +446,7 @@ impl<'a, 'p> List<'a, 'p> {
     pub fn iter(&self) -> ListIter<'a, 'p> {
         ListIter {
             buf: self.buf,
-            tokens: self.rest,
+            tokens: &self.tokens[1..],
             total: self.len(),
             index: 0,
             pos: 0,
@@ -491,7 +549,8 @@ impl<'a, 'p> Iterator for ListIter<'a, 'p> {
 pub struct Dict<'a, 'p> {
     buf: &'a [u8],
     token: &'p Token,
-    rest: &'p [Token],
+    /// `token` followed by the rest of the tape.
+    tokens: &'p [Token],
 }
 
 impl fmt::Debug for Dict<'_, '_> {
@@ -514,13 +573,19 @@ impl<'a, 'p> Dict<'a, 'p> {
     pub fn iter(&self) -> DictIter<'a, 'p> {
         DictIter {
             buf: self.buf,
-            tokens: self.rest,
+            tokens: &self.tokens[1..],
             total: self.len(),
             index: 0,
             pos: 0,
         }
     }
 
+    /// Returns the position of this dictionary in the source buffer.
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) fn pos(&self) -> usize {
+        self.token.start as usize
+    }
+
     /// Returns raw bytes of this dictionary.
     ///
     /// # Examples
@@ -540,9 +605,10 @@ impl<'a, 'p> Dict<'a, 'p> {
     }
 
     /// Returns the `Decoder` for the given key.
-    pub fn get(&self, key: &str) -> Option<Decoder<'a, 'p>> {
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<Decoder<'a, 'p>> {
+        let key = key.as_ref();
         self.iter().find_map(|(k, v)| {
-            if k.as_raw_bytes() == key.as_bytes() {
+            if k.as_raw_bytes() == key {
                 Some(v)
             } else {
                 None
@@ -551,32 +617,32 @@ impl<'a, 'p> Dict<'a, 'p> {
     }
 
     /// Returns the `Dict` for the given key.
-    pub fn get_dict(&self, key: &str) -> Option<Dict<'a, 'p>> {
+    pub fn get_dict<K: AsRef<[u8]>>(&self, key: K) -> Option<Dict<'a, 'p>> {
         self.get(key)?.into_dict()
     }
 
     /// Returns the `List` for the given key.
-    pub fn get_list(&self, key: &str) -> Option<List<'a, 'p>> {
+    pub fn get_list<K: AsRef<[u8]>>(&self, key: K) -> Option<List<'a, 'p>> {
         self.get(key)?.into_list()
     }
 
     /// Returns the byte slice for the given key.
-    pub fn get_bytes(&self, key: &str) -> Option<&'a [u8]> {
+    pub fn get_bytes<K: AsRef<[u8]>>(&self, key: K) -> Option<&'a [u8]> {
         self.get(key)?.as_bytes()
     }
 
     /// Returns the string slice for the given key.
-    pub fn get_str(&self, key: &str) -> Option<&'a str> {
+    pub fn get_str<K: AsRef<[u8]>>(&self, key: K) -> Option<&'a str> {
         self.get(key)?.as_str()
     }
 
     /// Returns the printable ASCII string slice for the given key.
-    pub fn get_ascii_str(&self, key: &str) -> Option<&'a str> {
+    pub fn get_ascii_str<K: AsRef<[u8]>>(&self, key: K) -> Option<&'a str> {
         self.get(key)?.as_ascii_str()
     }
 
     /// Returns the `i64` for the given key.
-    pub fn get_int(&self, key: &str) -> Option<i64> {
+    pub fn get_int<K: AsRef<[u8]>>(&self, key: K) -> Option<i64> {
         self.get(key)?.as_int()
     }
 
@@ -752,6 +818,20 @@ mod tests {
         assert_eq!(-12, n);
     }
 
+    #[test]
+    fn int_value_limits() {
+        let parser = &mut Parser::new();
+        let n = parser.parse::<i64>(b"i9223372036854775807e").unwrap();
+        assert_eq!(i64::MAX, n);
+        let n = parser.parse::<i64>(b"i-9223372036854775808e").unwrap();
+        assert_eq!(i64::MIN, n);
+
+        let err = parser.parse::<i64>(b"i9223372036854775808e").unwrap_err();
+        assert_eq!(Error::Overflow { pos: 20 }, err);
+        let err = parser.parse::<i64>(b"i-9223372036854775809e").unwrap_err();
+        assert_eq!(Error::Overflow { pos: 21 }, err);
+    }
+
     #[test]
     fn int_value_invalid() {
         let s = b"ixyze";
@@ -776,6 +856,16 @@ mod tests {
         assert_eq!(2, b.as_int().unwrap());
     }
 
+    #[test]
+    fn dict_get_binary_key() {
+        let s = b"d2:\x00\xffi1e2:\xff\x00i2ee";
+        let parser = &mut Parser::new();
+        let dict = parser.parse::<Dict>(s).unwrap();
+        assert_eq!(Some(1), dict.get_int([0x00, 0xff]));
+        assert_eq!(Some(2), dict.get_int(&b"\xff\x00"[..]));
+        assert_eq!(None, dict.get_int("a"));
+    }
+
     #[test]
     fn dict_get_invalid() {
         let s = b"d1:ai1e1:bi2ee";
@@ -871,4 +961,45 @@ mod tests {
         assert!(!list.is_empty());
         assert_eq!(list.len(), 3);
     }
+
+    #[test]
+    fn tape() {
+        let s = b"d1:ali1ei2ee1:bd1:ci3eee";
+        let parser = &mut Parser::new();
+        let decoder = parser.parse::<Decoder>(s).unwrap();
+        let tape = decoder.tape();
+        assert_eq!(parser_tokens(s), tape.to_vec());
+        assert_eq!(9, tape.len());
+
+        assert_eq!(TokenKind::Dict, tape[0].kind());
+        assert_eq!(2, tape[0].children());
+        assert_eq!(9, tape[0].subtree_len());
+        assert_eq!(0..s.len(), tape[0].range());
+
+        assert_eq!(TokenKind::List, tape[2].kind());
+        assert_eq!(2, tape[2].children());
+        assert_eq!(3, tape[2].subtree_len());
+        assert_eq!(&b"li1ei2ee"[..], &s[tape[2].range()]);
+
+        assert_eq!(TokenKind::Int, tape[4].kind());
+        assert_eq!(&b"2"[..], &s[tape[4].range()]);
+        assert_eq!(0, tape[4].children());
+        assert_eq!(1, tape[4].subtree_len());
+
+        let inner = decoder.at(6).unwrap();
+        assert_eq!(3, inner.tape().len());
+        assert_eq!(Some(3), inner.as_dict().unwrap().get_int("c"));
+        assert_eq!(Some(3), inner.at(2).unwrap().as_int());
+        assert!(inner.at(3).is_none());
+
+        let list = decoder.at(2).unwrap();
+        assert_eq!(&tape[2..5], list.tape());
+        assert_eq!(list.token(), &tape[2]);
+    }
+
+    fn parser_tokens(s: &[u8]) -> Vec<Token> {
+        let mut parser = Parser::new();
+        parser.parse::<Decoder>(s).unwrap();
+        parser.tokens().to_vec()
+    }
 }
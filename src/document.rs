@@ -0,0 +1,227 @@
+use crate::decode::{Decode, Decoder};
+use crate::error::Result;
+use crate::parse::Parser;
+use crate::token::Token;
+use alloc::sync::Arc;
+use core::fmt;
+
+/// A parsed bencode document which owns its buffer and tokens.
+///
+/// Unlike `Decoder`, which borrows both the input and the `Parser`, an
+/// `OwnedDocument` can be returned from functions or sent to other threads.
+/// Cloning is cheap as the buffer and tokens are shared.
+///
+/// Any buffer implementing `AsRef<[u8]>` can be used, e.g. `Vec<u8>`,
+/// `Box<[u8]>` or `bytes::Bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use ben::decode::Dict;
+/// use ben::OwnedDocument;
+///
+/// fn parse(buf: Vec<u8>) -> ben::Result<OwnedDocument> {
+///     OwnedDocument::parse(buf)
+/// }
+///
+/// let doc = parse(b"d1:ai1ee".to_vec()).unwrap();
+/// let handle = std::thread::spawn(move || {
+///     let dict = doc.decode::<Dict>().unwrap();
+///     dict.get_int("a")
+/// });
+/// assert_eq!(Some(1), handle.join().unwrap());
+/// ```
+#[derive(Clone)]
+pub struct OwnedDocument {
+    // Keeps the buffer alive and in place. It is never accessed mutably.
+    _buf: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    // The slice returned by the single `as_ref` call on `_buf`. The tokens
+    // were produced from this slice, so it is the only one they may index:
+    // a second call could return a different slice.
+    bytes: *const [u8],
+    tokens: Arc<[Token]>,
+}
+
+// Safety: `bytes` points into `_buf`, which is `Send + Sync` and shared
+// immutably, so it can be used from any thread that holds `_buf`.
+unsafe impl Send for OwnedDocument {}
+unsafe impl Sync for OwnedDocument {}
+
+impl OwnedDocument {
+    /// Parse given buffer with a default `Parser`.
+    ///
+    /// Trailing data after the bencoded object is an error, as with
+    /// `Parser::parse`.
+    pub fn parse<B>(buf: B) -> Result<Self>
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let mut parser = Parser::new();
+        let (buf, bytes) = Self::pin(buf);
+        // Safety: see `as_bytes`
+        parser.parse::<Decoder>(unsafe { &*bytes })?;
+        Ok(Self {
+            _buf: buf,
+            bytes,
+            tokens: parser.into_tokens().into(),
+        })
+    }
+
+    /// Parse given buffer with an existing parser, honouring its limits.
+    ///
+    /// The tokens are copied out so the parser can be reused.
+    pub fn parse_with<B>(parser: &mut Parser, buf: B) -> Result<Self>
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let (buf, bytes) = Self::pin(buf);
+        // Safety: see `as_bytes`
+        parser.parse::<Decoder>(unsafe { &*bytes })?;
+        Ok(Self {
+            _buf: buf,
+            bytes,
+            tokens: parser.tokens().into(),
+        })
+    }
+
+    /// Move the buffer to its final place and take its slice once.
+    fn pin<B>(buf: B) -> (Arc<dyn AsRef<[u8]> + Send + Sync>, *const [u8])
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let buf: Arc<dyn AsRef<[u8]> + Send + Sync> = Arc::new(buf);
+        let bytes: *const [u8] = (*buf).as_ref();
+        (buf, bytes)
+    }
+
+    /// Returns the document's buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        // Safety: `bytes` was borrowed from `_buf`, which lives as long as
+        // `self`, never moves inside its `Arc` and is only ever borrowed
+        // immutably, so the borrow is still valid
+        unsafe { &*self.bytes }
+    }
+
+    /// Returns the document's token tape.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Returns a decoder for the root value of the document.
+    pub fn decoder(&self) -> Decoder<'_, '_> {
+        // A successful parse always produces a root token
+        Decoder::new(self.as_bytes(), self.tokens()).unwrap()
+    }
+
+    /// Decode the root value of the document.
+    pub fn decode<'p, T: Decode<'p, 'p>>(&'p self) -> Result<T> {
+        T::decode(self.decoder())
+    }
+}
+
+impl fmt::Debug for OwnedDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.decoder().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Dict;
+    use crate::Error;
+
+    #[test]
+    fn parse() {
+        let doc = OwnedDocument::parse(b"d1:ai1e1:bl1:x1:yee".to_vec()).unwrap();
+        assert_eq!(b"d1:ai1e1:bl1:x1:yee", doc.as_bytes());
+        assert_eq!(7, doc.tokens().len());
+
+        let dict = doc.decode::<Dict>().unwrap();
+        assert_eq!(Some(1), dict.get_int("a"));
+        assert_eq!(Some("y"), dict.get_list("b").unwrap().get_str(1));
+        assert_eq!("{\"a\": 1, \"b\": [\"x\", \"y\"]}", format!("{:?}", doc));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let err = OwnedDocument::parse(b"d1:a".to_vec()).unwrap_err();
+        assert_eq!(Error::Eof, err);
+
+        let err = OwnedDocument::parse(&b"i1ei2e"[..]).unwrap_err();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Extra bytes at the end",
+                pos: 3
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn parse_with() {
+        let mut parser = Parser::new();
+        parser.token_limit(2);
+        let err = OwnedDocument::parse_with(&mut parser, b"li1ei2ee".to_vec()).unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 2 }, err);
+
+        let first = OwnedDocument::parse_with(&mut parser, b"li1ee".to_vec()).unwrap();
+        let second = OwnedDocument::parse_with(&mut parser, b"i2e".to_vec()).unwrap();
+        assert_eq!(Some(1), first.decoder().as_list().unwrap().get_int(0));
+        assert_eq!(Some(2), second.decoder().as_int());
+    }
+
+    #[test]
+    fn clone_shares_buffer() {
+        let doc = OwnedDocument::parse(b"4:spam".to_vec()).unwrap();
+        let clone = doc.clone();
+        assert_eq!(doc.as_bytes().as_ptr(), clone.as_bytes().as_ptr());
+        assert_eq!(doc.tokens().as_ptr(), clone.tokens().as_ptr());
+    }
+
+    #[test]
+    fn as_ref_called_once() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Returns the full buffer only on the first call.
+        struct Shrinking {
+            buf: Vec<u8>,
+            calls: AtomicUsize,
+        }
+
+        impl AsRef<[u8]> for Shrinking {
+            fn as_ref(&self) -> &[u8] {
+                if self.calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                    &self.buf
+                } else {
+                    &[]
+                }
+            }
+        }
+
+        let buf = Shrinking {
+            buf: b"l4:spami42ee".to_vec(),
+            calls: AtomicUsize::new(0),
+        };
+        let doc = OwnedDocument::parse(buf).unwrap();
+        assert_eq!(b"l4:spami42ee", doc.as_bytes());
+        let list = doc.decoder().into_list().unwrap();
+        assert_eq!(Some("spam"), list.get_str(0));
+        assert_eq!(Some(42), list.get_int(1));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn parse_bytes() {
+        let buf = bytes::Bytes::from_static(b"l4:spami42ee");
+        let doc = OwnedDocument::parse(buf.clone()).unwrap();
+        assert_eq!(buf.as_ptr(), doc.as_bytes().as_ptr());
+        assert_eq!(Some(42), doc.decoder().as_list().unwrap().get_int(1));
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<OwnedDocument>();
+    }
+}
@@ -1,5 +1,15 @@
+use crate::decode::{Decoder, Dict as DecodeDict, List as DecodeList};
+#[cfg(feature = "alloc")]
+use crate::error::{Error, Result};
+use crate::token::TokenKind;
+#[cfg(feature = "alloc")]
+use alloc::collections::BTreeMap;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String};
 use itoa::Buffer;
-use std::collections::BTreeMap;
+use sha1::digest::{Digest, Output};
 
 /// A trait for objects that can be bencoded.
 ///
@@ -10,82 +20,424 @@ pub trait Encode {
     fn encode<E: Encoder>(&self, enc: &mut E);
 
     /// Encode this value into a vector of bytes.
+    #[cfg(feature = "alloc")]
     fn encode_to_vec(&self) -> Vec<u8> {
-        let mut encoder = vec![];
+        let mut encoder = Vec::new();
         self.encode(&mut encoder);
         encoder
     }
+
+    /// Returns the exact number of bytes `encode` produces.
+    ///
+    /// This runs the encoding against a `Counter`, so no output buffer is
+    /// allocated. An `OrderedDict` still allocates for its keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::Encode;
+    ///
+    /// let value = vec!["Hello", "World"];
+    /// assert_eq!(value.encode_to_vec().len(), value.encoded_len());
+    /// ```
+    fn encoded_len(&self) -> usize {
+        let mut counter = Counter::new();
+        self.encode(&mut counter);
+        counter.len()
+    }
+}
+
+mod private {
+    /// Argument of the hidden `Sink` methods which keeps them from being
+    /// overridden outside this crate.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Internal;
+}
+
+/// A destination for encoded bytes.
+///
+/// Implement this to encode into buffers other than `Vec<u8>`. Every `Sink`
+/// is an `Encoder`, so `Encode` impls and the `List`, `Dict` and `BytesExact`
+/// builders work with any of them.
+///
+/// Writes are infallible. A sink which can run out of space, like
+/// `SliceSink`, has to remember the failure itself.
+pub trait Sink {
+    /// Append given bytes.
+    fn write(&mut self, bytes: &[u8]);
+
+    /// Append a single byte.
+    #[inline]
+    fn write_byte(&mut self, byte: u8) {
+        self.write(&[byte]);
+    }
+
+    /// Returns the byte count of a sink which only counts the written bytes.
+    ///
+    /// Builders which would otherwise buffer data, like `OrderedDict`, add
+    /// their length to this count directly instead. Only `Counter` and
+    /// `EntryBuf` override this; the `Internal` argument can't be named
+    /// outside this crate, so other sinks always return `None`.
+    #[doc(hidden)]
+    #[inline]
+    fn counter(&mut self, _: private::Internal) -> Option<&mut usize> {
+        None
+    }
+
+    /// Returns `true` if the sink checks the order of dictionary keys
+    /// itself and reports errors, so `Dict` skips its debug assertion.
+    #[doc(hidden)]
+    #[inline]
+    fn checks_keys(&self, _: private::Internal) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Sink for Vec<u8> {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+
+    #[inline]
+    fn write_byte(&mut self, byte: u8) {
+        self.push(byte);
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for &mut S {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        (**self).write(bytes);
+    }
+
+    #[inline]
+    fn write_byte(&mut self, byte: u8) {
+        (**self).write_byte(byte);
+    }
+
+    #[inline]
+    fn counter(&mut self, internal: private::Internal) -> Option<&mut usize> {
+        (**self).counter(internal)
+    }
+
+    #[inline]
+    fn checks_keys(&self, internal: private::Internal) -> bool {
+        (**self).checks_keys(internal)
+    }
+}
+
+/// A `Sink` which only counts the bytes written to it.
+///
+/// # Examples
+///
+/// ```
+/// use ben::encode::{Counter, Encoder};
+///
+/// let mut counter = Counter::new();
+/// counter.add_dict().add("key", "value");
+/// assert_eq!(b"d3:key5:valuee".len(), counter.len());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Counter {
+    len: usize,
+}
+
+impl Counter {
+    /// Create a new counter starting at 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if nothing was written yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Sink for Counter {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.len += bytes.len();
+    }
+
+    #[inline]
+    fn write_byte(&mut self, _byte: u8) {
+        self.len += 1;
+    }
+
+    #[inline]
+    fn counter(&mut self, _: private::Internal) -> Option<&mut usize> {
+        Some(&mut self.len)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Sink for bytes::BytesMut {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+/// A `Sink` writing into a fixed size byte slice, e.g. a stack buffer.
+///
+/// Writes past the end of the slice are dropped and mark the sink as
+/// overflowed.
+///
+/// # Examples
+///
+/// ```
+/// use ben::encode::{SliceSink, Encoder};
+///
+/// let mut buf = [0; 16];
+/// let mut sink = SliceSink::new(&mut buf);
+/// sink.add_str("Hello");
+/// assert_eq!(Some(&b"5:Hello"[..]), sink.written());
+/// ```
+pub struct SliceSink<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    overflow: bool,
+}
+
+impl<'a> SliceSink<'a> {
+    /// Create a new sink writing from the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        SliceSink {
+            buf,
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Returns the written bytes, or `None` if some bytes didn't fit.
+    pub fn written(&self) -> Option<&[u8]> {
+        if self.overflow {
+            None
+        } else {
+            Some(&self.buf[..self.len])
+        }
+    }
+
+    /// Returns `true` if some bytes didn't fit in the slice.
+    pub fn is_overflowed(&self) -> bool {
+        self.overflow
+    }
+}
+
+impl Sink for SliceSink<'_> {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.overflow || self.buf.len() - self.len < bytes.len() {
+            self.overflow = true;
+            return;
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+/// A `Sink` feeding the written bytes into a hash function such as
+/// `sha1::Sha1` or `sha2::Sha256`.
+///
+/// # Examples
+///
+/// ```
+/// use ben::encode::HashSink;
+/// use sha1::{Digest, Sha1};
+///
+/// let hash = HashSink::<Sha1>::hash(&vec![1, 2, 3]);
+/// assert_eq!(Sha1::digest(b"li1ei2ei3ee"), hash);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct HashSink<D> {
+    digest: D,
+}
+
+impl<D: Digest> HashSink<D> {
+    /// Create a new sink with a fresh hash state.
+    pub fn new() -> Self {
+        HashSink { digest: D::new() }
+    }
+
+    /// Returns the hash of all bytes written so far.
+    pub fn finalize(self) -> Output<D> {
+        self.digest.finalize()
+    }
+
+    /// Returns the hash of the encoding of `value`.
+    pub fn hash<E: Encode + ?Sized>(value: &E) -> Output<D> {
+        let mut sink = Self::new();
+        value.encode(&mut sink);
+        sink.finalize()
+    }
+}
+
+impl<D: Digest> Sink for HashSink<D> {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.digest.update(bytes);
+    }
+}
+
+/// A `Sink` writing the same bytes to two sinks.
+#[derive(Debug)]
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Sink, B: Sink> Tee<A, B> {
+    /// Create a new sink writing to both `first` and `second`.
+    pub fn new(first: A, second: B) -> Self {
+        Tee { first, second }
+    }
+
+    /// Returns the two sinks.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: Sink, B: Sink> Sink for Tee<A, B> {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.first.write(bytes);
+        self.second.write(bytes);
+    }
+
+    #[inline]
+    fn write_byte(&mut self, byte: u8) {
+        self.first.write_byte(byte);
+        self.second.write_byte(byte);
+    }
 }
 
 /// Bencode Encoder trait.
-pub trait Encoder: sealed::Sealed {
+///
+/// This is implemented for every `Sink`.
+pub trait Encoder: Sink + Sized {
     /// Encode an integer value.
-    fn add_int(&mut self, value: i64);
+    #[inline]
+    fn add_int(&mut self, value: i64) {
+        let mut buf = Buffer::new();
+        self.write_byte(b'i');
+        self.write(buf.format(value).as_bytes());
+        self.write_byte(b'e');
+    }
 
     /// Encode a byte slice.
-    fn add_bytes(&mut self, value: &[u8]);
+    #[inline]
+    fn add_bytes(&mut self, value: &[u8]) {
+        let mut buf = Buffer::new();
+        self.write(buf.format(value.len()).as_bytes());
+        self.write_byte(b':');
+        self.write(value);
+    }
 
     /// Create a new object which accepts exactly 'n' bytes lazily.
     ///
     /// The returned object's drop will panic if the total number of
     /// added bytes is not equal to 'n'.
-    fn add_bytes_exact(&mut self, len: usize) -> BytesExact<'_>;
+    #[inline]
+    fn add_bytes_exact(&mut self, len: usize) -> BytesExact<'_, Self> {
+        let mut buf = Buffer::new();
+        self.write(buf.format(len).as_bytes());
+        self.write_byte(b':');
+        BytesExact {
+            buf: self,
+            expected: len,
+            written: 0,
+        }
+    }
 
     /// Encode string slice.
-    fn add_str(&mut self, value: &str);
+    #[inline]
+    fn add_str(&mut self, value: &str) {
+        self.add_bytes(value.as_bytes());
+    }
 
     /// Create a new `List` in this `Encoder`.
-    fn add_list(&mut self) -> List<'_>;
+    #[inline]
+    fn add_list(&mut self) -> List<'_, Self> {
+        List::new(self)
+    }
 
     /// Create a new `Dict` in this `Encoder`.
-    fn add_dict(&mut self) -> Dict<'_>;
+    #[inline]
+    fn add_dict(&mut self) -> Dict<'_, Self> {
+        Dict::new(self)
+    }
 
     /// Create a new `OrderedDict` in this `Encoder`.
-    fn add_ordered_dict(&mut self) -> OrderedDict<'_, '_>;
-}
+    #[inline]
+    #[cfg(feature = "alloc")]
+    fn add_ordered_dict(&mut self) -> OrderedDict<'_, '_, Self> {
+        OrderedDict::new(self)
+    }
 
-mod sealed {
-    pub trait Sealed {}
+    /// Create a new `CheckedDict` in this `Encoder`.
+    ///
+    /// Unlike `add_dict`, this checks key order in all builds, including
+    /// the keys of values added to it. See `CheckedDict` for details.
+    #[inline]
+    #[cfg(feature = "alloc")]
+    fn add_checked_dict(&mut self) -> CheckedDict<'_, Self> {
+        CheckedDict::new(self)
+    }
 
-    impl Sealed for Vec<u8> {}
+    /// Create a new `CheckedList` in this `Encoder`.
+    #[inline]
+    #[cfg(feature = "alloc")]
+    fn add_checked_list(&mut self) -> CheckedList<'_, Self> {
+        CheckedList::new(self)
+    }
 }
 
+impl<S: Sink> Encoder for S {}
+
 /// Add bytes lazily to given encoder.
 ///
 /// # Panic
 /// Drop will panic if the expected number of bytes
 /// is not equal to actually added bytes.
-pub struct BytesExact<'a> {
-    buf: &'a mut Vec<u8>,
+pub struct BytesExact<'a, S> {
+    buf: &'a mut S,
     expected: usize,
     written: usize,
 }
 
-impl BytesExact<'_> {
+impl<S: Sink> BytesExact<'_, S> {
     /// Add given byte slice.
     pub fn add(&mut self, buf: &[u8]) {
         self.written += buf.len();
-        self.buf.extend(buf);
+        self.buf.write(buf);
     }
 
     pub fn finish(self) {}
 }
 
-impl Drop for BytesExact<'_> {
+impl<S> Drop for BytesExact<'_, S> {
     fn drop(&mut self) {
         assert_eq!(self.expected, self.written);
     }
 }
 
 /// Bencode List representation.
-pub struct List<'a> {
-    buf: &'a mut Vec<u8>,
+pub struct List<'a, S: Sink> {
+    buf: &'a mut S,
 }
 
-impl List<'_> {
+impl<S: Sink> List<'_, S> {
     /// Create a new list
-    fn new(buf: &mut Vec<u8>) -> List<'_> {
-        buf.push(b'l');
+    fn new(buf: &mut S) -> List<'_, S> {
+        buf.write_byte(b'l');
         List { buf }
     }
 
@@ -96,22 +448,23 @@ impl List<'_> {
 
     /// Create a new object which accepts exactly given number of
     /// bytes lazily.
-    pub fn add_bytes_exact(&mut self, len: usize) -> BytesExact<'_> {
+    pub fn add_bytes_exact(&mut self, len: usize) -> BytesExact<'_, S> {
         self.buf.add_bytes_exact(len)
     }
 
     /// Create a new `List` in this list.
-    pub fn add_list(&mut self) -> List<'_> {
+    pub fn add_list(&mut self) -> List<'_, S> {
         self.buf.add_list()
     }
 
     /// Create a new `Dict` in this list.
-    pub fn add_dict(&mut self) -> Dict<'_> {
+    pub fn add_dict(&mut self) -> Dict<'_, S> {
         self.buf.add_dict()
     }
 
     /// Create a new `OrderedDict` in this list.
-    pub fn add_ordered_dict(&mut self) -> OrderedDict<'_, '_> {
+    #[cfg(feature = "alloc")]
+    pub fn add_ordered_dict(&mut self) -> OrderedDict<'_, '_, S> {
         self.buf.add_ordered_dict()
     }
 
@@ -119,9 +472,9 @@ impl List<'_> {
     pub fn finish(self) {}
 }
 
-impl Drop for List<'_> {
+impl<S: Sink> Drop for List<'_, S> {
     fn drop(&mut self) {
-        self.buf.push(b'e');
+        self.buf.write_byte(b'e');
     }
 }
 
@@ -129,215 +482,679 @@ impl Drop for List<'_> {
 ///
 /// Note: This will not enforce order or uniqueness of keys.
 /// These invariants have to be maintained by the caller. If the keys
-/// are not known beforehand, use `OrderedDict` instead.
+/// are not known beforehand, use `OrderedDict` instead, or `CheckedDict`
+/// to get an error for misordered keys in release builds too.
 ///
 /// If the invariants don't meet in debug mode, the add calls will
-/// panic.
-pub struct Dict<'a> {
-    buf: &'a mut Vec<u8>,
+/// panic. The check needs the `alloc` feature.
+///
+/// Keys are arbitrary byte strings and may be given as anything which
+/// implements `AsRef<[u8]>`, e.g. `&str` or a raw `[u8; 20]` hash.
+pub struct Dict<'a, S: Sink> {
+    buf: &'a mut S,
 
-    #[cfg(debug_assertions)]
+    #[cfg(all(debug_assertions, feature = "alloc"))]
     last_key: Option<Vec<u8>>,
 }
 
-impl Dict<'_> {
+impl<S: Sink> Dict<'_, S> {
     /// Create a new dict
-    fn new(buf: &mut Vec<u8>) -> Dict<'_> {
-        buf.push(b'd');
+    fn new(buf: &mut S) -> Dict<'_, S> {
+        buf.write_byte(b'd');
         Dict {
             buf,
-            #[cfg(debug_assertions)]
+            #[cfg(all(debug_assertions, feature = "alloc"))]
             last_key: None,
         }
     }
 
     /// `Encode` the value for given key inside this dictionary.
-    pub fn add<E: Encode>(&mut self, key: &str, value: E) {
+    pub fn add<K: AsRef<[u8]>, E: Encode>(&mut self, key: K, value: E) {
         self.add_key(key);
         value.encode(self.buf);
     }
 
+    /// `Encode` the value for given key inside this dictionary and return
+    /// the hash of the value's encoding.
+    ///
+    /// The value is hashed while it is written, e.g. to get the info-hash
+    /// of the `info` dictionary while the metainfo file streams to its
+    /// destination.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::encode::{Encoder, HashSink};
+    /// use sha1::Sha1;
+    ///
+    /// let info = vec!["Hello"];
+    /// let mut buf = vec![];
+    /// let mut dict = buf.add_dict();
+    /// let hash = dict.add_hashed::<_, _, Sha1>("info", &info);
+    /// dict.finish();
+    /// assert_eq!(HashSink::<Sha1>::hash(&info), hash);
+    /// assert_eq!(b"d4:infol5:Helloee", &buf[..]);
+    /// ```
+    pub fn add_hashed<K: AsRef<[u8]>, E: Encode, D: Digest>(
+        &mut self,
+        key: K,
+        value: E,
+    ) -> Output<D> {
+        self.add_key(key);
+        let mut tee = Tee::new(&mut *self.buf, HashSink::<D>::new());
+        value.encode(&mut tee);
+        tee.into_inner().1.finalize()
+    }
+
     /// Create a new object which accepts exactly given number of
     /// bytes lazily.
-    pub fn add_bytes_exact(&mut self, key: &str, len: usize) -> BytesExact<'_> {
+    pub fn add_bytes_exact<K: AsRef<[u8]>>(&mut self, key: K, len: usize) -> BytesExact<'_, S> {
         self.add_key(key);
         self.buf.add_bytes_exact(len)
     }
 
     /// Create a new `List` for given key inside this dictionary.
-    pub fn add_list(&mut self, key: &str) -> List<'_> {
+    pub fn add_list<K: AsRef<[u8]>>(&mut self, key: K) -> List<'_, S> {
         self.add_key(key);
         self.buf.add_list()
     }
 
     /// Create a new `Dict` for given key inside this dictionary.
-    pub fn add_dict(&mut self, key: &str) -> Dict<'_> {
+    pub fn add_dict<K: AsRef<[u8]>>(&mut self, key: K) -> Dict<'_, S> {
         self.add_key(key);
         self.buf.add_dict()
     }
 
     /// Create a new `OrderedDict` inside this dictionary.
-    pub fn add_ordered_dict(&mut self, key: &str) -> OrderedDict<'_, '_> {
+    #[cfg(feature = "alloc")]
+    pub fn add_ordered_dict<K: AsRef<[u8]>>(&mut self, key: K) -> OrderedDict<'_, '_, S> {
         self.add_key(key);
         self.buf.add_ordered_dict()
     }
 
-    fn add_key(&mut self, key: &str) {
+    fn add_key<K: AsRef<[u8]>>(&mut self, key: K) {
+        let key = key.as_ref();
         self.assert_key_ordering(key);
-        self.buf.add_str(key);
+        self.buf.add_bytes(key);
     }
 
-    #[cfg(debug_assertions)]
-    fn assert_key_ordering(&mut self, key: &str) {
-        let key = key.as_bytes();
-        if let Some(last_key) = &mut self.last_key {
-            if key < &last_key[..] {
-                panic!("Keys must be sorted");
-            }
-            if key == &last_key[..] {
-                panic!("Keys must be unique");
-            }
-            last_key.clear();
-            last_key.extend(key);
-        } else {
-            self.last_key = Some(key.to_vec());
+    #[cfg(all(debug_assertions, feature = "alloc"))]
+    fn assert_key_ordering(&mut self, key: &[u8]) {
+        if self.buf.checks_keys(private::Internal) {
+            return;
+        }
+        if let Err(e) = check_key_ordering(&mut self.last_key, key) {
+            panic!("{}", e);
         }
     }
 
-    #[cfg(not(debug_assertions))]
-    fn assert_key_ordering(&mut self, _key: &str) {}
+    #[cfg(not(all(debug_assertions, feature = "alloc")))]
+    fn assert_key_ordering(&mut self, _key: &[u8]) {}
 
     /// Finish building this dictionary.
     pub fn finish(self) {}
 }
 
-impl Drop for Dict<'_> {
+impl<S: Sink> Drop for Dict<'_, S> {
     fn drop(&mut self) {
-        self.buf.push(b'e');
+        self.buf.write_byte(b'e');
     }
 }
 
-/// Bencode Ordered Dictionary representation.
+/// Check that `key` sorts strictly after `last_key` and make it the last key.
+#[cfg(feature = "alloc")]
+fn check_key_ordering(last_key: &mut Option<Vec<u8>>, key: &[u8]) -> Result<()> {
+    if let Some(last_key) = last_key {
+        compare_keys(last_key, key)?;
+        last_key.clear();
+        last_key.extend(key);
+    } else {
+        *last_key = Some(key.to_vec());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+fn compare_keys(last_key: &[u8], key: &[u8]) -> Result<()> {
+    if key < last_key {
+        return Err(Error::Other("Keys must be sorted"));
+    }
+    if key == last_key {
+        return Err(Error::Other("Keys must be unique"));
+    }
+    Ok(())
+}
+
+/// A `Sink` which checks that the bytes written through it form exactly
+/// one bencode value whose dictionaries have sorted and unique keys.
 ///
-/// This will maintain keys to be unique and sorted.
-pub struct OrderedDict<'a, 'k> {
-    buf: &'a mut Vec<u8>,
-    entries: BTreeMap<&'k [u8], Vec<u8>>,
+/// The first error is kept and returned by `finish`. Bytes are passed on to
+/// the inner sink as they come, so output after an error is invalid.
+#[cfg(feature = "alloc")]
+struct KeyCheck<S: Sink> {
+    sink: S,
+    pos: usize,
+    state: CheckState,
+    stack: Vec<CheckFrame>,
+    key: Vec<u8>,
+    done: bool,
+    error: Option<Error>,
+}
+
+#[cfg(feature = "alloc")]
+enum CheckState {
+    Next,
+    IntStart,
+    Int { neg: bool, n: Option<u64> },
+    Len(usize),
+    Str { remaining: usize, is_key: bool },
 }
 
-impl<'a, 'k> OrderedDict<'a, 'k> {
+#[cfg(feature = "alloc")]
+enum CheckFrame {
+    List,
+    Dict {
+        last_key: Option<Vec<u8>>,
+        value_next: bool,
+    },
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Sink> KeyCheck<S> {
+    fn new(sink: S) -> Self {
+        KeyCheck {
+            sink,
+            pos: 0,
+            state: CheckState::Next,
+            stack: Vec::new(),
+            key: Vec::new(),
+            done: false,
+            error: None,
+        }
+    }
+
+    /// Returns the inner sink if exactly one valid value was written.
+    fn finish(self) -> Result<S> {
+        match self.error {
+            Some(e) => Err(e),
+            None if !self.done => Err(Error::Eof),
+            None => Ok(self.sink),
+        }
+    }
+
+    fn check(&mut self, bytes: &[u8]) {
+        let mut i = 0;
+        while i < bytes.len() && self.error.is_none() {
+            if let CheckState::Str { remaining, is_key } = self.state {
+                let n = remaining.min(bytes.len() - i);
+                if is_key {
+                    self.key.extend_from_slice(&bytes[i..i + n]);
+                }
+                i += n;
+                self.pos += n;
+                if n == remaining {
+                    self.end_string(is_key);
+                } else {
+                    self.state = CheckState::Str {
+                        remaining: remaining - n,
+                        is_key,
+                    };
+                }
+                continue;
+            }
+
+            if let Err(e) = self.check_byte(bytes[i]) {
+                self.error = Some(e);
+            }
+            i += 1;
+            self.pos += 1;
+        }
+    }
+
+    fn check_byte(&mut self, byte: u8) -> Result<()> {
+        let pos = self.pos;
+        match self.state {
+            CheckState::Next => {
+                let (in_dict, key_next) = match self.stack.last() {
+                    Some(CheckFrame::Dict { value_next, .. }) => (true, !value_next),
+                    _ => (false, false),
+                };
+                match byte {
+                    _ if self.done => return Err(Error::Unexpected { pos }),
+                    b'0'..=b'9' => self.state = CheckState::Len(usize::from(byte - b'0')),
+                    b'e' if key_next || (!in_dict && !self.stack.is_empty()) => {
+                        self.stack.pop();
+                        self.end_value();
+                    }
+                    _ if key_next => return Err(Error::Unexpected { pos }),
+                    b'i' => self.state = CheckState::IntStart,
+                    b'l' => self.stack.push(CheckFrame::List),
+                    b'd' => self.stack.push(CheckFrame::Dict {
+                        last_key: None,
+                        value_next: false,
+                    }),
+                    _ => return Err(Error::Unexpected { pos }),
+                }
+            }
+            CheckState::IntStart => match byte {
+                b'-' => self.state = CheckState::Int { neg: true, n: None },
+                b'0'..=b'9' => {
+                    self.state = CheckState::Int {
+                        neg: false,
+                        n: Some(u64::from(byte - b'0')),
+                    }
+                }
+                _ => return Err(Error::Unexpected { pos }),
+            },
+            CheckState::Int { neg, n } => match byte {
+                b'0'..=b'9' => {
+                    let max = i64::MAX as u64 + u64::from(neg);
+                    let n = n
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(u64::from(byte - b'0')))
+                        .filter(|&n| n <= max)
+                        .ok_or(Error::Overflow { pos })?;
+                    self.state = CheckState::Int { neg, n: Some(n) };
+                }
+                b'e' if n.is_some() => self.end_value(),
+                _ => return Err(Error::Unexpected { pos }),
+            },
+            CheckState::Len(len) => match byte {
+                b'0'..=b'9' => {
+                    let len = len
+                        .checked_mul(10)
+                        .and_then(|len| len.checked_add(usize::from(byte - b'0')))
+                        .ok_or(Error::Overflow { pos })?;
+                    self.state = CheckState::Len(len);
+                }
+                b':' => {
+                    let is_key = matches!(
+                        self.stack.last(),
+                        Some(CheckFrame::Dict {
+                            value_next: false,
+                            ..
+                        })
+                    );
+                    self.key.clear();
+                    if len == 0 {
+                        self.end_string(is_key);
+                    } else {
+                        self.state = CheckState::Str {
+                            remaining: len,
+                            is_key,
+                        };
+                    }
+                }
+                _ => return Err(Error::Unexpected { pos }),
+            },
+            CheckState::Str { .. } => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn end_string(&mut self, is_key: bool) {
+        self.state = CheckState::Next;
+        if !is_key {
+            return self.end_value();
+        }
+        if let Some(CheckFrame::Dict {
+            last_key,
+            value_next,
+        }) = self.stack.last_mut()
+        {
+            match check_key_ordering(last_key, &self.key) {
+                Ok(()) => *value_next = true,
+                Err(e) => self.error = Some(e),
+            }
+        }
+    }
+
+    fn end_value(&mut self) {
+        self.state = CheckState::Next;
+        match self.stack.last_mut() {
+            Some(CheckFrame::Dict { value_next, .. }) => *value_next = false,
+            Some(CheckFrame::List) => {}
+            None => self.done = true,
+        }
+    }
+}
+
+/// Encode `value` into a new buffer, checking it like `CheckedDict::add`.
+#[cfg(feature = "alloc")]
+pub(crate) fn encode_checked<E: Encode>(value: E) -> Result<Vec<u8>> {
+    let mut check = KeyCheck::new(Vec::new());
+    value.encode(&mut check);
+    check.finish()
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Sink> Sink for KeyCheck<S> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.check(bytes);
+        self.sink.write(bytes);
+    }
+
+    fn checks_keys(&self, _: private::Internal) -> bool {
+        true
+    }
+}
+
+/// Bencode Dictionary representation which checks the order and uniqueness
+/// of keys in all builds.
+///
+/// Adding a key which does not sort strictly after the previous one
+/// returns an error and writes nothing, so the dictionary stays valid and
+/// the caller may go on with other keys. Dictionaries and lists created
+/// from it check their keys as well.
+///
+/// Values added with `add` or `add_hashed` are checked while they are
+/// encoded: they must be exactly one bencode value whose dictionaries have
+/// sorted and unique keys at every level, including plain `Dict`s built by
+/// their `Encode` impls, which then return the error instead of panicking
+/// in debug builds. The value is written as it is checked, so after such
+/// an error the output is invalid and should be discarded.
+///
+/// # Examples
+///
+/// ```
+/// use ben::Encoder;
+///
+/// let mut buf = vec![];
+/// let mut dict = buf.add_checked_dict();
+/// let mut list = dict.add_list("a").unwrap();
+/// let mut inner = list.add_dict();
+/// inner.add("x", 1).unwrap();
+/// assert!(inner.add("x", 2).is_err());
+/// inner.finish();
+/// list.finish();
+/// assert!(dict.add("a", 3).is_err());
+/// dict.add("b", 4).unwrap();
+/// dict.finish();
+/// assert_eq!(b"d1:ald1:xi1eee1:bi4ee", &buf[..]);
+/// ```
+#[cfg(feature = "alloc")]
+pub struct CheckedDict<'a, S: Sink> {
+    buf: &'a mut S,
+    last_key: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Sink> CheckedDict<'_, S> {
     /// Create a new dict
-    fn new(buf: &'a mut Vec<u8>) -> OrderedDict<'a, 'k> {
-        OrderedDict {
+    fn new(buf: &mut S) -> CheckedDict<'_, S> {
+        buf.write_byte(b'd');
+        CheckedDict {
             buf,
-            entries: BTreeMap::new(),
+            last_key: None,
         }
     }
 
-    /// Create a new `List` for given key inside this dictionary.
-    pub fn add_list(&mut self, key: &'k str) -> List<'_> {
-        self.add_key(key).add_list()
+    /// `Encode` the value for given key inside this dictionary.
+    pub fn add<K: AsRef<[u8]>, E: Encode>(&mut self, key: K, value: E) -> Result<()> {
+        self.add_key(key)?;
+        let mut check = KeyCheck::new(&mut *self.buf);
+        value.encode(&mut check);
+        check.finish().map(drop)
     }
 
-    /// Create a new `Dict` for given key inside this dictionary.
-    pub fn add_dict(&mut self, key: &'k str) -> Dict<'_> {
-        self.add_key(key).add_dict()
+    /// `Encode` the value for given key inside this dictionary and return
+    /// the hash of the value's encoding.
+    pub fn add_hashed<K: AsRef<[u8]>, E: Encode, D: Digest>(
+        &mut self,
+        key: K,
+        value: E,
+    ) -> Result<Output<D>> {
+        self.add_key(key)?;
+        let mut check = KeyCheck::new(Tee::new(&mut *self.buf, HashSink::<D>::new()));
+        value.encode(&mut check);
+        Ok(check.finish()?.into_inner().1.finalize())
     }
 
-    /// Create a new `OrderedDict` inside this dictionary.
-    pub fn add_ordered_dict(&mut self, key: &'k str) -> OrderedDict<'_, '_> {
-        self.add_key(key).add_ordered_dict()
+    /// Create a new object which accepts exactly given number of
+    /// bytes lazily.
+    pub fn add_bytes_exact<K: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        len: usize,
+    ) -> Result<BytesExact<'_, S>> {
+        self.add_key(key)?;
+        Ok(self.buf.add_bytes_exact(len))
     }
 
-    /// `Encode` the value for given key inside this dictionary.
-    pub fn add<E: Encode>(&mut self, key: &'k str, value: E) {
-        let buf = self.add_key(key);
-        value.encode(buf);
+    /// Create a new `CheckedList` for given key inside this dictionary.
+    pub fn add_list<K: AsRef<[u8]>>(&mut self, key: K) -> Result<CheckedList<'_, S>> {
+        self.add_key(key)?;
+        Ok(CheckedList::new(self.buf))
     }
 
-    fn add_key(&mut self, key: &'k str) -> &mut Vec<u8> {
-        let buf = self.entries.entry(key.as_bytes()).or_insert_with(Vec::new);
-        buf.clear();
-        buf
+    /// Create a new `CheckedDict` for given key inside this dictionary.
+    pub fn add_dict<K: AsRef<[u8]>>(&mut self, key: K) -> Result<CheckedDict<'_, S>> {
+        self.add_key(key)?;
+        Ok(CheckedDict::new(self.buf))
+    }
+
+    fn add_key<K: AsRef<[u8]>>(&mut self, key: K) -> Result<()> {
+        let key = key.as_ref();
+        check_key_ordering(&mut self.last_key, key)?;
+        self.buf.add_bytes(key);
+        Ok(())
     }
 
     /// Finish building this dictionary.
     pub fn finish(self) {}
 }
 
-impl Drop for OrderedDict<'_, '_> {
+#[cfg(feature = "alloc")]
+impl<S: Sink> Drop for CheckedDict<'_, S> {
     fn drop(&mut self) {
-        self.buf.push(b'd');
-        for (k, v) in &self.entries {
-            self.buf.add_bytes(k);
-            self.buf.extend(v);
-        }
-        self.buf.push(b'e');
+        self.buf.write_byte(b'e');
     }
 }
 
-impl Encoder for Vec<u8> {
-    #[inline]
-    fn add_int(&mut self, value: i64) {
-        self.push(b'i');
-        let mut buf = Buffer::new();
-        self.extend(buf.format(value).as_bytes());
-        self.push(b'e');
+/// Bencode List representation whose nested dictionaries are
+/// `CheckedDict`s.
+///
+/// Values added with `add` are checked like those of `CheckedDict::add`.
+#[cfg(feature = "alloc")]
+pub struct CheckedList<'a, S: Sink> {
+    buf: &'a mut S,
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Sink> CheckedList<'_, S> {
+    /// Create a new list
+    fn new(buf: &mut S) -> CheckedList<'_, S> {
+        buf.write_byte(b'l');
+        CheckedList { buf }
     }
 
-    #[inline]
-    fn add_bytes(&mut self, value: &[u8]) {
-        let mut buf = Buffer::new();
-        self.extend(buf.format(value.len()).as_bytes());
-        self.push(b':');
-        self.extend(value);
+    /// `Encode` a value in this list.
+    pub fn add<E: Encode>(&mut self, value: E) -> Result<()> {
+        let mut check = KeyCheck::new(&mut *self.buf);
+        value.encode(&mut check);
+        check.finish().map(drop)
+    }
+
+    /// Create a new object which accepts exactly given number of
+    /// bytes lazily.
+    pub fn add_bytes_exact(&mut self, len: usize) -> BytesExact<'_, S> {
+        self.buf.add_bytes_exact(len)
+    }
+
+    /// Create a new `CheckedList` in this list.
+    pub fn add_list(&mut self) -> CheckedList<'_, S> {
+        CheckedList::new(self.buf)
+    }
+
+    /// Create a new `CheckedDict` in this list.
+    pub fn add_dict(&mut self) -> CheckedDict<'_, S> {
+        CheckedDict::new(self.buf)
+    }
+
+    /// Finish building this list.
+    pub fn finish(self) {}
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Sink> Drop for CheckedList<'_, S> {
+    fn drop(&mut self) {
+        self.buf.write_byte(b'e');
     }
+}
+
+/// Bencode Ordered Dictionary representation.
+///
+/// This will maintain keys to be unique and sorted. Keys are compared as
+/// raw byte strings.
+///
+/// Values are buffered in an `EntryBuf` each until the dictionary is
+/// dropped. If the underlying sink only counts bytes, as for
+/// `Encode::encoded_len`, only the lengths of the values are kept, but the
+/// keys still go into a `BTreeMap` so a repeated key replaces the earlier
+/// value. Counting therefore allocates once per distinct key.
+#[cfg(feature = "alloc")]
+pub struct OrderedDict<'a, 'k, S: Sink> {
+    buf: &'a mut S,
+    entries: BTreeMap<&'k [u8], EntryBuf>,
+    count_only: bool,
+    checks_keys: bool,
+}
 
+/// Buffer for a single value of an `OrderedDict`.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default)]
+pub struct EntryBuf {
+    bytes: Vec<u8>,
+    len: usize,
+    count_only: bool,
+    checks_keys: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl Sink for EntryBuf {
     #[inline]
-    fn add_bytes_exact(&mut self, len: usize) -> BytesExact<'_> {
-        let mut buf = Buffer::new();
-        self.extend(buf.format(len).as_bytes());
-        self.push(b':');
-        BytesExact {
-            buf: self,
-            expected: len,
-            written: 0,
+    fn write(&mut self, bytes: &[u8]) {
+        self.len += bytes.len();
+        if !self.count_only {
+            self.bytes.extend_from_slice(bytes);
         }
     }
 
     #[inline]
-    fn add_str(&mut self, value: &str) {
-        self.add_bytes(value.as_bytes());
+    fn counter(&mut self, _: private::Internal) -> Option<&mut usize> {
+        if self.count_only {
+            Some(&mut self.len)
+        } else {
+            None
+        }
     }
 
     #[inline]
-    fn add_list(&mut self) -> List<'_> {
-        List::new(self)
+    fn checks_keys(&self, _: private::Internal) -> bool {
+        self.checks_keys
     }
+}
 
-    #[inline]
-    fn add_dict(&mut self) -> Dict<'_> {
-        Dict::new(self)
+#[cfg(feature = "alloc")]
+impl<'a, 'k, S: Sink> OrderedDict<'a, 'k, S> {
+    /// Create a new dict
+    fn new(buf: &'a mut S) -> OrderedDict<'a, 'k, S> {
+        let count_only = buf.counter(private::Internal).is_some();
+        let checks_keys = buf.checks_keys(private::Internal);
+        OrderedDict {
+            buf,
+            entries: BTreeMap::new(),
+            count_only,
+            checks_keys,
+        }
     }
 
-    #[inline]
-    fn add_ordered_dict(&mut self) -> OrderedDict<'_, '_> {
-        OrderedDict::new(self)
+    /// Create a new `List` for given key inside this dictionary.
+    pub fn add_list<K: AsRef<[u8]> + ?Sized>(&mut self, key: &'k K) -> List<'_, EntryBuf> {
+        self.add_key(key).add_list()
+    }
+
+    /// Create a new `Dict` for given key inside this dictionary.
+    pub fn add_dict<K: AsRef<[u8]> + ?Sized>(&mut self, key: &'k K) -> Dict<'_, EntryBuf> {
+        self.add_key(key).add_dict()
+    }
+
+    /// Create a new `OrderedDict` inside this dictionary.
+    pub fn add_ordered_dict<K: AsRef<[u8]> + ?Sized>(
+        &mut self,
+        key: &'k K,
+    ) -> OrderedDict<'_, '_, EntryBuf> {
+        self.add_key(key).add_ordered_dict()
+    }
+
+    /// `Encode` the value for given key inside this dictionary.
+    pub fn add<K: AsRef<[u8]> + ?Sized, E: Encode>(&mut self, key: &'k K, value: E) {
+        let buf = self.add_key(key);
+        value.encode(buf);
+    }
+
+    /// `Encode` the value for given key inside this dictionary and return
+    /// the hash of the value's encoding.
+    pub fn add_hashed<K: AsRef<[u8]> + ?Sized, E: Encode, D: Digest>(
+        &mut self,
+        key: &'k K,
+        value: E,
+    ) -> Output<D> {
+        let buf = self.add_key(key);
+        let mut tee = Tee::new(buf, HashSink::<D>::new());
+        value.encode(&mut tee);
+        tee.into_inner().1.finalize()
+    }
+
+    fn add_key<K: AsRef<[u8]> + ?Sized>(&mut self, key: &'k K) -> &mut EntryBuf {
+        let buf = self.entries.entry(key.as_ref()).or_default();
+        buf.bytes.clear();
+        buf.len = 0;
+        buf.count_only = self.count_only;
+        buf.checks_keys = self.checks_keys;
+        buf
+    }
+
+    /// Finish building this dictionary.
+    pub fn finish(self) {}
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Sink> Drop for OrderedDict<'_, '_, S> {
+    fn drop(&mut self) {
+        if let Some(count) = self.buf.counter(private::Internal) {
+            let mut buf = Buffer::new();
+            *count += 2;
+            for (k, v) in &self.entries {
+                *count += buf.format(k.len()).len() + 1 + k.len() + v.len;
+            }
+            return;
+        }
+
+        self.buf.write_byte(b'd');
+        for (k, v) in &self.entries {
+            self.buf.add_bytes(k);
+            self.buf.write(&v.bytes);
+        }
+        self.buf.write_byte(b'e');
     }
 }
 
 impl<T: Encode> Encode for &T {
     fn encode<E: Encoder>(&self, enc: &mut E) {
-        (&**self).encode(enc);
+        (**self).encode(enc);
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<T: Encode> Encode for Box<T> {
     fn encode<E: Encoder>(&self, enc: &mut E) {
-        (&**self).encode(enc);
+        (**self).encode(enc);
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<T: Encode> Encode for Vec<T> {
     fn encode<E: Encoder>(&self, enc: &mut E) {
         let mut list = enc.add_list();
@@ -370,6 +1187,7 @@ impl Encode for &str {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl Encode for String {
     fn encode<E: Encoder>(&self, enc: &mut E) {
         enc.add_str(self);
@@ -399,6 +1217,39 @@ impl_arr![
     26, 27, 28, 29, 30, 31, 32, 64, 128, 256, 512, 1024
 ];
 
+/// Re-encodes a parsed value.
+///
+/// Lists and dictionaries are written as their source bytes, so parsed
+/// input passes through unchanged even if its dictionary keys are not
+/// sorted. Use `value::Value` to normalize a document instead.
+impl Encode for Decoder<'_, '_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        match self.token.kind {
+            TokenKind::List | TokenKind::Dict => enc.write(self.as_raw_bytes()),
+            TokenKind::Int => {
+                enc.write_byte(b'i');
+                enc.write(self.as_raw_bytes());
+                enc.write_byte(b'e');
+            }
+            TokenKind::ByteStr => enc.add_bytes(self.as_raw_bytes()),
+        }
+    }
+}
+
+/// Writes the list's source bytes.
+impl Encode for DecodeList<'_, '_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        enc.write(self.as_raw_bytes());
+    }
+}
+
+/// Writes the dictionary's source bytes, keeping the source key order.
+impl Encode for DecodeDict<'_, '_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        enc.write(self.as_raw_bytes());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -470,6 +1321,172 @@ mod tests {
         assert_eq!(&b"d1:a5:Hello1:b5:Worlde"[..], &e[..]);
     }
 
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl Sink for Chunks {
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.push(bytes.to_vec());
+        }
+    }
+
+    #[test]
+    fn encode_custom_sink() {
+        let mut sink = Chunks(vec![]);
+        {
+            let mut dict = sink.add_dict();
+            dict.add("a", 1);
+            let mut list = dict.add_list("b");
+            list.add("x");
+            list.add_bytes_exact(2).add(b"yz");
+        }
+        assert_eq!(b"d1:ai1e1:bl1:x2:yzee", &sink.0.concat()[..]);
+    }
+
+    #[test]
+    fn encode_slice_sink() {
+        let mut buf = [0; 8];
+        let mut sink = SliceSink::new(&mut buf);
+        sink.add_list().add(42);
+        assert_eq!(Some(&b"li42ee"[..]), sink.written());
+
+        sink.add_str("too long");
+        assert!(sink.is_overflowed());
+        assert_eq!(None, sink.written());
+    }
+
+    #[test]
+    fn encode_ordered_dict_custom_sink() {
+        let mut sink = Chunks(vec![]);
+        {
+            let mut dict = sink.add_ordered_dict();
+            dict.add_list("b").add(2);
+            dict.add("a", 1);
+        }
+        assert_eq!(b"d1:ai1e1:bli2eee", &sink.0.concat()[..]);
+    }
+
+    struct Nested;
+
+    impl Encode for Nested {
+        fn encode<E: Encoder>(&self, enc: &mut E) {
+            let mut dict = enc.add_ordered_dict();
+            dict.add("z", 1);
+            dict.add("a", "replaced");
+            dict.add("a", "Hello, World");
+            {
+                let mut inner = dict.add_ordered_dict("m");
+                inner.add("y", vec![1, 22, 333]);
+                inner.add_dict("x").add_bytes_exact("k", 3).add(b"abc");
+            }
+            dict.add_list("l").add_list().add(-1);
+        }
+    }
+
+    #[test]
+    fn encoded_len() {
+        assert_eq!(4, 42.encoded_len());
+        assert_eq!(7, "Hello".encoded_len());
+        assert_eq!(Nested.encode_to_vec().len(), Nested.encoded_len());
+    }
+
+    #[test]
+    fn encoded_len_does_not_buffer() {
+        let mut counter = Counter::new();
+        let mut dict = counter.add_ordered_dict();
+        dict.add("a", [0u8; 1024]);
+        assert!(dict.entries[&b"a"[..]].bytes.is_empty());
+        assert_eq!(1029, dict.entries[&b"a"[..]].len);
+        dict.finish();
+        assert_eq!(b"d1:a".len() + 1029 + 1, counter.len());
+    }
+
+    #[test]
+    fn encode_hashed() {
+        use sha2::Sha256;
+
+        let mut counter = Counter::new();
+        let mut buf = vec![];
+        let (hash, ordered_hash) = {
+            let mut tee = Tee::new(&mut buf, &mut counter);
+            let mut dict = tee.add_ordered_dict();
+            let ordered_hash = dict.add_hashed::<_, _, Sha256>("b", &Nested);
+            dict.add("a", 1);
+            dict.finish();
+
+            let mut dict = tee.add_dict();
+            let hash = dict.add_hashed::<_, _, Sha256>("info", &Nested);
+            (hash, ordered_hash)
+        };
+
+        let expected = HashSink::<Sha256>::hash(&Nested);
+        assert_eq!(expected, hash);
+        assert_eq!(expected, ordered_hash);
+        assert_eq!(buf.len(), counter.len());
+        assert!(buf.ends_with(&[b"d4:info", &Nested.encode_to_vec()[..], b"e"].concat()));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn encode_bytes_mut() {
+        let mut buf = bytes::BytesMut::new();
+        buf.add_dict().add("a", "b");
+        assert_eq!(&b"d1:a1:be"[..], &buf[..]);
+    }
+
+    #[test]
+    fn encode_decoder() {
+        let s = b"d1:ai-12e1:bl0:1:xd1:ci0eee2:\xff\x00i1ee";
+        let parser = &mut crate::Parser::new();
+        let decoder = parser.parse::<Decoder>(s).unwrap();
+        assert_eq!(&s[..], &decoder.encode_to_vec()[..]);
+    }
+
+    #[test]
+    fn encode_decoder_unsorted() {
+        let s = b"ld1:bi1e1:ad1:zi0e1:y0:eei-0e03:abce";
+        let parser = &mut crate::Parser::new();
+        let decoder = parser.parse::<Decoder>(s).unwrap();
+        assert_eq!(&s[..], &decoder.encode_to_vec()[..]);
+        assert_eq!(s.len(), decoder.encoded_len());
+
+        let list = decoder.as_list().unwrap();
+        let dict = list.get_dict(0).unwrap();
+        assert_eq!(dict.as_raw_bytes(), &dict.encode_to_vec()[..]);
+        assert_eq!(list.as_raw_bytes(), &list.encode_to_vec()[..]);
+        assert_eq!(b"i-0e", &list.get(1).unwrap().encode_to_vec()[..]);
+        assert_eq!(b"3:abc", &list.get(2).unwrap().encode_to_vec()[..]);
+    }
+
+    #[test]
+    fn encode_dict_binary_keys() {
+        let mut e = vec![];
+        let mut dict = e.add_dict();
+        dict.add(&b"\x00\x01"[..], 1);
+        dict.add([0xffu8; 2], 2);
+        dict.add_list(&[0xff, 0xff, 0x00][..]).add("a");
+        dict.finish();
+        assert_eq!(
+            &b"d2:\x00\x01i1e2:\xff\xffi2e3:\xff\xff\x00l1:aee"[..],
+            &e[..]
+        );
+    }
+
+    #[test]
+    fn encode_dict_ordered_binary_keys() {
+        let a = [0xffu8; 4];
+        let b = [0x00u8; 4];
+        let mut e = vec![];
+        let mut dict = e.add_ordered_dict();
+        dict.add(&a, 1);
+        dict.add("b", 2);
+        dict.add(&b[..], 3);
+        dict.finish();
+        assert_eq!(
+            &b"d4:\x00\x00\x00\x00i3e1:bi2e4:\xff\xff\xff\xffi1ee"[..],
+            &e[..]
+        );
+    }
+
     #[test]
     fn encode_list() {
         let mut e = vec![];
@@ -546,6 +1563,138 @@ mod tests {
         bytes.add(&[0; 100]);
     }
 
+    #[test]
+    fn encode_checked_dict() {
+        let mut e = vec![];
+        let mut dict = e.add_checked_dict();
+        dict.add("b", 1).unwrap();
+        assert_eq!(Err(Error::Other("Keys must be sorted")), dict.add("a", 2));
+        assert_eq!(Err(Error::Other("Keys must be unique")), dict.add("b", 3));
+        assert!(dict.add_bytes_exact("b", 1).is_err());
+        assert!(dict.add_hashed::<_, _, sha1::Sha1>("a", 4).is_err());
+        dict.add([0xffu8], 5).unwrap();
+        dict.finish();
+        assert_eq!(b"d1:bi1e1:\xffi5ee", &e[..]);
+    }
+
+    #[test]
+    fn encode_checked_nested() {
+        let mut e = vec![];
+        let mut dict = e.add_checked_dict();
+        let mut inner = dict.add_dict("a").unwrap();
+        inner.add("y", 1).unwrap();
+        assert!(inner.add("x", 2).is_err());
+        inner.finish();
+        assert!(dict.add_list("a").is_err());
+
+        let mut list = dict.add_list("b").unwrap();
+        let mut nested = list.add_list();
+        let mut entry = nested.add_dict();
+        entry.add("k", 1).unwrap();
+        assert!(entry.add("k", 2).is_err());
+        drop(entry);
+        nested.finish();
+        list.add("v").unwrap();
+        list.finish();
+        drop(dict);
+
+        assert_eq!(b"d1:ad1:yi1ee1:blld1:ki1eee1:vee", &e[..]);
+        crate::Parser::new().parse::<Decoder>(&e).unwrap();
+    }
+
+    #[test]
+    fn encode_checked_values() {
+        struct Raw(&'static [u8]);
+
+        impl Encode for Raw {
+            fn encode<E: Encoder>(&self, enc: &mut E) {
+                enc.write(self.0);
+            }
+        }
+
+        let check = |raw: &'static [u8]| {
+            let mut e = vec![];
+            let mut dict = e.add_checked_dict();
+            dict.add("a", Raw(raw))
+        };
+        assert_eq!(
+            Err(Error::Other("Keys must be sorted")),
+            check(b"ld1:bi1e1:ai2eee")
+        );
+        assert_eq!(
+            Err(Error::Other("Keys must be unique")),
+            check(b"d1:ad1:xi1e1:xi1eee")
+        );
+        assert_eq!(Err(Error::Eof), check(b"i1"));
+        assert_eq!(Err(Error::Eof), check(b""));
+        assert_eq!(Err(Error::Unexpected { pos: 3 }), check(b"i1ei2e"));
+        assert_eq!(Err(Error::Unexpected { pos: 1 }), check(b"die"));
+        assert_eq!(Err(Error::Unexpected { pos: 4 }), check(b"d1:ae"));
+        assert_eq!(Err(Error::Unexpected { pos: 1 }), check(b"iei"));
+        assert_eq!(
+            Err(Error::Overflow { pos: 19 }),
+            check(b"i9223372036854775808e")
+        );
+        check(b"i-9223372036854775808e").unwrap();
+        check(b"d0:le1:ad0:0:e1:b3:xyze").unwrap();
+
+        let mut e = vec![];
+        let mut dict = e.add_checked_dict();
+        let hash = dict
+            .add_hashed::<_, _, sha1::Sha1>("a", Raw(b"d1:ai1e1:bi2ee"))
+            .unwrap();
+        assert_eq!(sha1::Sha1::digest(b"d1:ai1e1:bi2ee"), hash);
+        let mut list = dict.add_list("b").unwrap();
+        list.add(Raw(b"d1:ai1ee")).unwrap();
+        list.finish();
+        dict.finish();
+        assert_eq!(b"d1:ad1:ai1e1:bi2ee1:bld1:ai1eeee", &e[..]);
+    }
+
+    #[test]
+    fn encode_checked_builders() {
+        struct Unsorted;
+
+        impl Encode for Unsorted {
+            fn encode<E: Encoder>(&self, enc: &mut E) {
+                let mut dict = enc.add_dict();
+                dict.add("b", 1);
+                dict.add("a", 2);
+            }
+        }
+
+        struct Nested;
+
+        impl Encode for Nested {
+            fn encode<E: Encoder>(&self, enc: &mut E) {
+                let mut dict = enc.add_ordered_dict();
+                dict.add("z", 1);
+                dict.add("y", Unsorted);
+            }
+        }
+
+        let mut e = vec![];
+        let mut list = e.add_checked_list();
+        assert_eq!(Err(Error::Other("Keys must be sorted")), list.add(Unsorted));
+        assert_eq!(Err(Error::Other("Keys must be sorted")), list.add(Nested));
+        assert_eq!(
+            Err(Error::Other("Keys must be sorted")),
+            list.add(vec![Unsorted])
+        );
+    }
+
+    #[test]
+    fn encode_checked_list() {
+        let mut e = vec![];
+        let mut list = e.add_checked_list();
+        let mut dict = list.add_dict();
+        dict.add("a", 1).unwrap();
+        assert!(dict.add("a", 1).is_err());
+        dict.finish();
+        list.finish();
+        assert_eq!(b"ld1:ai1eee", &e[..]);
+    }
+
     #[cfg(debug_assertions)]
     mod debug {
         use super::*;
@@ -568,6 +1717,15 @@ mod tests {
             dict.add("a", "World");
         }
 
+        #[test]
+        #[should_panic(expected = "Keys must be sorted")]
+        fn encode_dict_unordered_binary() {
+            let mut e = vec![];
+            let mut dict = e.add_dict();
+            dict.add([0xffu8], "Hello");
+            dict.add([0x00u8], "World");
+        }
+
         #[test]
         fn encode_dict_sorted() {
             let mut e = vec![];
@@ -1,8 +1,13 @@
-use std::fmt;
+use core::fmt;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
+/// Errors returned by parsing and decoding.
+///
+/// New variants may be added in minor releases, so matches need a
+/// wildcard arm.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// The string is not a full Bencode packet, more bytes expected
     Eof,
@@ -19,9 +24,15 @@ pub enum Error {
     /// Exceeded Depth limit
     DepthLimit { limit: usize },
 
+    /// Input longer than the token offsets can address
+    BufferLimit { limit: usize },
+
     /// Integer Overflow
     Overflow { pos: usize },
 
+    /// Required dictionary key is missing from the dictionary at given position
+    Missing { key: &'static str, pos: usize },
+
     /// Type mismatch
     TypeMismatch(&'static str),
 
@@ -37,11 +48,25 @@ impl fmt::Display for Error {
             Self::Invalid { reason, pos } => write!(f, "Invalid input at {}: {}", pos, reason),
             Self::TokenLimit { limit } => write!(f, "Exceeded Token limit of {}", limit),
             Self::DepthLimit { limit } => write!(f, "Exceeded Depth limit of {}", limit),
+            Self::BufferLimit { limit } => {
+                write!(f, "Exceeded buffer length limit of {} bytes", limit)
+            }
             Self::Overflow { pos } => write!(f, "Integer overflow at {}", pos),
+            Self::Missing { key, pos } => {
+                write!(f, "Missing key `{}` in dictionary at {}", key, pos)
+            }
             Self::TypeMismatch(reason) => write!(f, "Type mismatch: {}", reason),
             Self::Other(reason) => f.write_str(reason),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {}
+
+impl Error {
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) fn missing(key: &'static str, pos: usize) -> Self {
+        Self::Missing { key, pos }
+    }
+}
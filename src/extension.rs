@@ -0,0 +1,395 @@
+//! Peer-wire extension protocol (BEP 10).
+//!
+//! Extension messages are regular length-prefixed peer messages with id 20,
+//! followed by a one byte extension id and a bencoded dictionary. Some
+//! extensions append raw data after the dictionary.
+
+use crate::decode::{Decode, Decoder, Dict};
+use crate::encode::{Encode, Encoder, Sink};
+use crate::error::{Error, Result};
+use crate::parse::Parser;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Peer-wire message id of extension messages.
+pub const MSG_EXTENDED: u8 = 20;
+
+/// Extension id of the extension handshake.
+pub const HANDSHAKE_ID: u8 = 0;
+
+/// The extension handshake, sent with extension id 0.
+///
+/// All fields are optional on the wire. Like most peers, decoding ignores
+/// optional fields with invalid values, such as an out-of-range port. A
+/// malformed `m` dictionary is an error since the message ids can't be
+/// trusted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Handshake<'a> {
+    /// Supported extensions and the message ids the sender wants to receive
+    /// them on (`m`). An id of 0 disables a previously enabled extension.
+    pub extensions: Vec<(&'a str, u8)>,
+
+    /// Local TCP listen port (`p`).
+    pub port: Option<u16>,
+
+    /// Client name and version (`v`).
+    pub client: Option<&'a str>,
+
+    /// The receiver's IP address as seen by the sender (`yourip`).
+    pub your_ip: Option<IpAddr>,
+
+    /// The sender's IPv4 address (`ipv4`).
+    pub ipv4: Option<Ipv4Addr>,
+
+    /// The sender's IPv6 address (`ipv6`).
+    pub ipv6: Option<Ipv6Addr>,
+
+    /// Number of outstanding requests the sender supports (`reqq`).
+    pub reqq: Option<i64>,
+
+    /// Size of the info dictionary for ut_metadata (`metadata_size`).
+    pub metadata_size: Option<i64>,
+}
+
+impl Handshake<'_> {
+    /// Returns the message id the sender registered for given extension.
+    ///
+    /// Returns `None` if the extension is not supported or was disabled.
+    pub fn extension_id(&self, name: &str) -> Option<u8> {
+        self.extensions
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, id)| *id)
+            .filter(|id| *id != 0)
+    }
+}
+
+impl<'a, 'p> Decode<'a, 'p> for Handshake<'a> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let dict = Dict::decode(decoder)?;
+
+        let mut extensions = vec![];
+        if let Some(m) = dict.get("m") {
+            for (name, id) in Dict::decode(m)? {
+                let pos = name.pos();
+                let name = name.as_str().ok_or(Error::Invalid {
+                    reason: "Extension name is not a UTF-8 string",
+                    pos,
+                })?;
+                let id = match id.as_int() {
+                    Some(id) if (0..=i64::from(u8::MAX)).contains(&id) => id as u8,
+                    _ => {
+                        return Err(Error::Invalid {
+                            reason: "Extension id out of range",
+                            pos: id.pos(),
+                        })
+                    }
+                };
+                extensions.push((name, id));
+            }
+        }
+
+        let port = dict
+            .get_int("p")
+            .filter(|port| (1..=i64::from(u16::MAX)).contains(port))
+            .map(|port| port as u16);
+
+        let your_ip = match dict.get_bytes("yourip") {
+            Some(ip) if ip.len() == 4 => Some(IpAddr::V4(ipv4(ip))),
+            Some(ip) if ip.len() == 16 => Some(IpAddr::V6(ipv6(ip))),
+            _ => None,
+        };
+
+        Ok(Handshake {
+            extensions,
+            port,
+            client: dict.get_str("v"),
+            your_ip,
+            ipv4: dict.get_bytes("ipv4").filter(|ip| ip.len() == 4).map(ipv4),
+            ipv6: dict.get_bytes("ipv6").filter(|ip| ip.len() == 16).map(ipv6),
+            reqq: dict.get_int("reqq"),
+            metadata_size: dict.get_int("metadata_size"),
+        })
+    }
+}
+
+impl Encode for Handshake<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let mut dict = enc.add_dict();
+        if let Some(ip) = &self.ipv4 {
+            dict.add("ipv4", ip.octets());
+        }
+        if let Some(ip) = &self.ipv6 {
+            dict.add("ipv6", ip.octets());
+        }
+        {
+            let mut m = dict.add_ordered_dict("m");
+            for (name, id) in &self.extensions {
+                m.add(*name, i64::from(*id));
+            }
+        }
+        if let Some(size) = self.metadata_size {
+            dict.add("metadata_size", size);
+        }
+        if let Some(port) = self.port {
+            dict.add("p", i64::from(port));
+        }
+        if let Some(reqq) = self.reqq {
+            dict.add("reqq", reqq);
+        }
+        if let Some(client) = self.client {
+            dict.add("v", client);
+        }
+        match &self.your_ip {
+            Some(IpAddr::V4(ip)) => dict.add("yourip", ip.octets()),
+            Some(IpAddr::V6(ip)) => dict.add("yourip", ip.octets()),
+            None => {}
+        }
+    }
+}
+
+fn ipv4(bytes: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])
+}
+
+fn ipv6(bytes: &[u8]) -> Ipv6Addr {
+    let mut octets = [0; 16];
+    octets.copy_from_slice(bytes);
+    Ipv6Addr::from(octets)
+}
+
+/// Write a complete extension message to `sink`: the 4 byte big-endian
+/// length prefix, message id 20, the extension id, the bencoded payload and
+/// any trailing data.
+///
+/// The length prefix is computed with `Encode::encoded_len` up front, so the
+/// message is streamed without a temporary buffer. Returns an error without
+/// writing anything if the message is too long for the length prefix.
+///
+/// # Examples
+///
+/// ```
+/// use ben::extension::{self, Handshake, HANDSHAKE_ID};
+///
+/// let handshake = Handshake {
+///     extensions: vec![("ut_metadata", 3)],
+///     ..Handshake::default()
+/// };
+/// let mut buf = vec![];
+/// extension::encode_message(&mut buf, HANDSHAKE_ID, &handshake, &[]).unwrap();
+/// assert_eq!(&buf[..6], &[0, 0, 0, 26, 20, 0]);
+/// assert_eq!(&buf[6..], &b"d1:md11:ut_metadatai3eee"[..]);
+/// ```
+pub fn encode_message<S: Sink, T: Encode>(
+    sink: &mut S,
+    id: u8,
+    payload: &T,
+    trailer: &[u8],
+) -> Result<()> {
+    let len = payload
+        .encoded_len()
+        .checked_add(2)
+        .and_then(|len| len.checked_add(trailer.len()))
+        .and_then(|len| u32::try_from(len).ok())
+        .ok_or(Error::Other("Message too long"))?;
+    sink.write(&len.to_be_bytes());
+    sink.write_byte(MSG_EXTENDED);
+    sink.write_byte(id);
+    payload.encode(sink);
+    sink.write(trailer);
+    Ok(())
+}
+
+/// Split one complete extension message from the beginning of `buf`.
+///
+/// Returns the extension id, the message body (bencoded payload plus any
+/// trailing data) and the total number of bytes consumed including the
+/// length prefix. Returns `Error::Eof` if `buf` doesn't hold the whole
+/// message yet.
+pub fn split_message(buf: &[u8]) -> Result<(u8, &[u8], usize)> {
+    if buf.len() < 4 {
+        return Err(Error::Eof);
+    }
+    let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    let end = len.checked_add(4).ok_or(Error::Overflow { pos: 0 })?;
+    if buf.len() < end {
+        return Err(Error::Eof);
+    }
+    if len < 2 {
+        return Err(Error::Invalid {
+            reason: "Message too short for an extension message",
+            pos: 0,
+        });
+    }
+    if buf[4] != MSG_EXTENDED {
+        return Err(Error::Invalid {
+            reason: "Not an extension message",
+            pos: 4,
+        });
+    }
+    Ok((buf[5], &buf[6..end], end))
+}
+
+/// Split and decode one complete extension message from the beginning of
+/// `buf`.
+///
+/// The bencoded payload is parsed with `Parser::parse_prefix` so data after
+/// the dictionary is returned as the trailer rather than rejected. Returns
+/// the extension id, the decoded payload, the trailer and the total number
+/// of bytes consumed.
+pub fn decode_message<'a, 'p, T>(
+    parser: &'p mut Parser,
+    buf: &'a [u8],
+) -> Result<(u8, T, &'a [u8], usize)>
+where
+    T: Decode<'a, 'p>,
+{
+    let (id, body, consumed) = split_message(buf)?;
+    let (payload, len) = parser.parse_prefix::<T>(body)?;
+    Ok((id, payload, &body[len..], consumed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_round_trip() {
+        let handshake = Handshake {
+            extensions: vec![("ut_pex", 1), ("ut_metadata", 2), ("lt_donthave", 0)],
+            port: Some(6881),
+            client: Some("ben 0.2"),
+            your_ip: Some("10.1.2.3".parse().unwrap()),
+            ipv4: Some("192.168.0.1".parse().unwrap()),
+            ipv6: Some("::1".parse().unwrap()),
+            reqq: Some(250),
+            metadata_size: Some(31235),
+        };
+
+        let buf = handshake.encode_to_vec();
+        let parser = &mut Parser::new();
+        let decoded = parser.parse::<Handshake>(&buf).unwrap();
+
+        // Extensions come back sorted by name
+        assert_eq!(
+            vec![("lt_donthave", 0), ("ut_metadata", 2), ("ut_pex", 1)],
+            decoded.extensions
+        );
+        assert_eq!(Some(2), decoded.extension_id("ut_metadata"));
+        assert_eq!(None, decoded.extension_id("lt_donthave"));
+        assert_eq!(None, decoded.extension_id("unknown"));
+        assert_eq!(
+            Handshake {
+                extensions: decoded.extensions.clone(),
+                ..handshake
+            },
+            decoded
+        );
+    }
+
+    #[test]
+    fn handshake_unknown_keys() {
+        let s = b"d1:md6:ut_pexi1ee1:xi1e6:yourip16:\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01e";
+        let parser = &mut Parser::new();
+        let handshake = parser.parse::<Handshake>(&s[..]).unwrap();
+        assert_eq!(Some(1), handshake.extension_id("ut_pex"));
+        assert_eq!(Some("::1".parse().unwrap()), handshake.your_ip);
+        assert_eq!(None, handshake.port);
+    }
+
+    #[test]
+    fn handshake_invalid_id() {
+        let parser = &mut Parser::new();
+        let err = parser.parse::<Handshake>(b"d1:md1:ai256eee").unwrap_err();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Extension id out of range",
+                pos: 9,
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn handshake_invalid_port() {
+        let parser = &mut Parser::new();
+        for s in [&b"d1:pi0ee"[..], b"d1:pi65536ee", b"d1:p4:6881e"] {
+            let handshake = parser.parse::<Handshake>(s).unwrap();
+            assert_eq!(None, handshake.port);
+        }
+        let handshake = parser.parse::<Handshake>(b"d1:pi6881ee").unwrap();
+        assert_eq!(Some(6881), handshake.port);
+    }
+
+    #[test]
+    fn message_too_long() {
+        struct Huge;
+
+        impl Encode for Huge {
+            fn encode<E: Encoder>(&self, _: &mut E) {
+                unreachable!()
+            }
+
+            fn encoded_len(&self) -> usize {
+                u32::MAX as usize - 1
+            }
+        }
+
+        let mut buf = vec![];
+        assert_eq!(
+            Err(Error::Other("Message too long")),
+            encode_message(&mut buf, 1, &Huge, &[])
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let handshake = Handshake {
+            extensions: vec![("ut_metadata", 3)],
+            metadata_size: Some(100),
+            ..Handshake::default()
+        };
+
+        let mut buf = vec![];
+        encode_message(&mut buf, HANDSHAKE_ID, &handshake, &[]).unwrap();
+        encode_message(&mut buf, 3, &handshake, b"trailer").unwrap();
+
+        let parser = &mut Parser::new();
+        let (id, decoded, trailer, n) = decode_message::<Handshake>(parser, &buf).unwrap();
+        assert_eq!(HANDSHAKE_ID, id);
+        assert_eq!(handshake, decoded);
+        assert!(trailer.is_empty());
+
+        let rest = &buf[n..];
+        let (id, decoded, trailer, n) = decode_message::<Handshake>(parser, rest).unwrap();
+        assert_eq!(3, id);
+        assert_eq!(handshake, decoded);
+        assert_eq!(b"trailer", trailer);
+        assert_eq!(rest.len(), n);
+    }
+
+    #[test]
+    fn split_incomplete() {
+        let mut buf = vec![];
+        encode_message(&mut buf, 1, &Handshake::default(), &[]).unwrap();
+        for i in 0..buf.len() {
+            assert_eq!(Err(Error::Eof), split_message(&buf[..i]));
+        }
+        assert!(split_message(&buf).is_ok());
+    }
+
+    #[test]
+    fn split_not_extended() {
+        let buf = [0, 0, 0, 5, 4, 0, 0, 0, 1];
+        assert_eq!(
+            Err(Error::Invalid {
+                reason: "Not an extension message",
+                pos: 4,
+            }),
+            split_message(&buf)
+        );
+    }
+}
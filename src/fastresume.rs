@@ -0,0 +1,533 @@
+//! libtorrent compatible fast-resume files.
+//!
+//! A fast-resume file records the state of a torrent between sessions so the
+//! payload doesn't have to be re-checked on startup. `FastResume` decodes the
+//! fields most clients care about into owned values. Every other key is kept
+//! as raw bencode and written back unchanged, so reading and re-writing a file
+//! produced by a newer libtorrent doesn't lose any state.
+
+use crate::decode::{Decode, Decoder, Dict, List};
+use crate::encode::{encode_checked, Encode, Encoder};
+use crate::error::{Error, Result};
+use crate::tracker::{decode_compact_addr, COMPACT_V4_LEN, COMPACT_V6_LEN};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::SocketAddr;
+
+/// Value of the `file-format` key.
+pub const FILE_FORMAT: &str = "libtorrent resume file";
+
+/// Value written to the `file-version` key if `FastResume::file_version`
+/// is unset.
+pub const FILE_VERSION: i64 = 1;
+
+/// Flag set in a `pieces` entry if the piece is downloaded.
+pub const PIECE_HAVE: u8 = 0x01;
+
+/// A fast-resume document.
+///
+/// Empty lists are omitted when encoding, unset optional fields are never
+/// written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FastResume {
+    /// Format version (`file-version`), kept as decoded.
+    pub file_version: Option<i64>,
+
+    /// v1 info-hash of the torrent (`info-hash`).
+    pub info_hash: Option<[u8; 20]>,
+
+    /// v2 info-hash of the torrent (`info-hash2`).
+    pub info_hash_v2: Option<[u8; 32]>,
+
+    /// Torrent name (`name`).
+    pub name: Option<String>,
+
+    /// Directory the payload is saved in (`save_path`).
+    pub save_path: Option<String>,
+
+    /// One byte of flags per piece (`pieces`), see `PIECE_HAVE`.
+    pub pieces: Vec<u8>,
+
+    /// Download priority of each file, in file order (`file_priority`).
+    pub file_priority: Vec<i64>,
+
+    /// Known peers (`peers` and `peers6`).
+    pub peers: Vec<SocketAddr>,
+
+    /// Announce URLs grouped in tiers (`trackers`).
+    pub trackers: Vec<Vec<String>>,
+
+    /// Web seed URLs (`url-list`).
+    pub url_list: Vec<String>,
+
+    /// Posix time the torrent was added (`added_time`).
+    pub added_time: Option<i64>,
+
+    /// Posix time the download completed (`completed_time`).
+    pub completed_time: Option<i64>,
+
+    /// Posix time a complete copy was last seen in the swarm
+    /// (`last_seen_complete`).
+    pub last_seen_complete: Option<i64>,
+
+    /// Seconds the torrent has been active (`active_time`).
+    pub active_time: Option<i64>,
+
+    /// Seconds the torrent has been finished (`finished_time`).
+    pub finished_time: Option<i64>,
+
+    /// Seconds the torrent has been seeding (`seeding_time`).
+    pub seeding_time: Option<i64>,
+
+    /// Total payload bytes uploaded (`total_uploaded`).
+    pub total_uploaded: Option<i64>,
+
+    /// Total payload bytes downloaded (`total_downloaded`).
+    pub total_downloaded: Option<i64>,
+
+    extra: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl FastResume {
+    /// Returns `true` if piece `index` is marked as downloaded.
+    pub fn has_piece(&self, index: usize) -> bool {
+        self.pieces
+            .get(index)
+            .is_some_and(|flags| flags & PIECE_HAVE != 0)
+    }
+
+    /// Marks piece `index` as downloaded or not, growing `pieces` if needed.
+    ///
+    /// Other flags of the piece are left untouched.
+    pub fn set_piece(&mut self, index: usize, have: bool) {
+        if self.pieces.len() <= index {
+            self.pieces.resize(index + 1, 0);
+        }
+        if have {
+            self.pieces[index] |= PIECE_HAVE;
+        } else {
+            self.pieces[index] &= !PIECE_HAVE;
+        }
+    }
+
+    /// Returns the raw bencoded value of a key not covered by the typed
+    /// fields.
+    pub fn extra<K: AsRef<[u8]>>(&self, key: K) -> Option<&[u8]> {
+        self.extra.get(key.as_ref()).map(Vec::as_slice)
+    }
+
+    /// Sets a key not covered by the typed fields to the encoding of
+    /// `value`, replacing any previous value.
+    ///
+    /// Returns an error if `key` belongs to one of the typed fields, or if
+    /// `value` doesn't encode to exactly one bencode value with sorted and
+    /// unique dictionary keys.
+    pub fn set_extra<K: AsRef<[u8]>, E: Encode>(&mut self, key: K, value: E) -> Result<()> {
+        let key = key.as_ref();
+        if TYPED_KEYS.iter().any(|k| k.as_bytes() == key) {
+            return Err(Error::Other("Key belongs to a typed field"));
+        }
+        self.extra.insert(key.to_vec(), encode_checked(value)?);
+        Ok(())
+    }
+
+    /// Removes a key not covered by the typed fields and returns its raw
+    /// bencoded value.
+    pub fn remove_extra<K: AsRef<[u8]>>(&mut self, key: K) -> Option<Vec<u8>> {
+        self.extra.remove(key.as_ref())
+    }
+
+    /// Returns an iterator over the keys not covered by the typed fields and
+    /// their raw bencoded values, sorted by key.
+    pub fn extras(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.extra
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value.as_slice()))
+    }
+}
+
+impl<'a, 'p> Decode<'a, 'p> for FastResume {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let pos = decoder.pos();
+        let dict = Dict::decode(decoder)?;
+
+        match dict.get("file-format") {
+            Some(format) if format.as_bytes() == Some(FILE_FORMAT.as_bytes()) => {}
+            Some(format) => {
+                return Err(Error::Invalid {
+                    reason: "Not a libtorrent resume file",
+                    pos: format.pos(),
+                })
+            }
+            None => return Err(Error::missing("file-format", pos)),
+        }
+
+        let mut resume = FastResume::default();
+        for (key, value) in dict.iter() {
+            match key.as_raw_bytes() {
+                b"file-format" => {}
+                b"file-version" => resume.file_version = Some(int(&value)?),
+                b"info-hash" => resume.info_hash = Some(hash(&value)?),
+                b"info-hash2" => resume.info_hash_v2 = Some(hash(&value)?),
+                b"name" => resume.name = Some(string(&value)?),
+                b"save_path" => resume.save_path = Some(string(&value)?),
+                b"pieces" => resume.pieces = bytes(&value)?.to_vec(),
+                b"file_priority" => {
+                    resume.file_priority = list(value)?
+                        .iter()
+                        .map(|v| int(&v))
+                        .collect::<Result<_>>()?
+                }
+                b"peers" => compact_peers(&value, COMPACT_V4_LEN, &mut resume.peers)?,
+                b"peers6" => compact_peers(&value, COMPACT_V6_LEN, &mut resume.peers)?,
+                b"trackers" => {
+                    for tier in list(value)? {
+                        resume.trackers.push(strings(tier)?);
+                    }
+                }
+                b"url-list" => resume.url_list = strings(value)?,
+                b"added_time" => resume.added_time = Some(int(&value)?),
+                b"completed_time" => resume.completed_time = Some(int(&value)?),
+                b"last_seen_complete" => resume.last_seen_complete = Some(int(&value)?),
+                b"active_time" => resume.active_time = Some(int(&value)?),
+                b"finished_time" => resume.finished_time = Some(int(&value)?),
+                b"seeding_time" => resume.seeding_time = Some(int(&value)?),
+                b"total_uploaded" => resume.total_uploaded = Some(int(&value)?),
+                b"total_downloaded" => resume.total_downloaded = Some(int(&value)?),
+                key => {
+                    // Keeps the source bytes, even of dicts with unsorted keys
+                    resume.extra.insert(key.to_vec(), value.encode_to_vec());
+                }
+            }
+        }
+
+        Ok(resume)
+    }
+}
+
+impl Encode for FastResume {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let mut dict = enc.add_ordered_dict();
+
+        dict.add("file-format", FILE_FORMAT);
+        dict.add("file-version", self.file_version.unwrap_or(FILE_VERSION));
+        if let Some(info_hash) = &self.info_hash {
+            dict.add("info-hash", info_hash);
+        }
+        if let Some(info_hash) = &self.info_hash_v2 {
+            dict.add("info-hash2", info_hash);
+        }
+        if let Some(name) = &self.name {
+            dict.add("name", name.as_str());
+        }
+        if let Some(save_path) = &self.save_path {
+            dict.add("save_path", save_path.as_str());
+        }
+        if !self.pieces.is_empty() {
+            dict.add("pieces", self.pieces.as_slice());
+        }
+        if !self.file_priority.is_empty() {
+            dict.add("file_priority", &self.file_priority);
+        }
+
+        let (mut peers, mut peers6) = (vec![], vec![]);
+        for addr in &self.peers {
+            match addr {
+                SocketAddr::V4(addr) => {
+                    peers.extend(&addr.ip().octets());
+                    peers.extend(&addr.port().to_be_bytes());
+                }
+                SocketAddr::V6(addr) => {
+                    peers6.extend(&addr.ip().octets());
+                    peers6.extend(&addr.port().to_be_bytes());
+                }
+            }
+        }
+        if !peers.is_empty() {
+            dict.add("peers", peers.as_slice());
+        }
+        if !peers6.is_empty() {
+            dict.add("peers6", peers6.as_slice());
+        }
+
+        if !self.trackers.is_empty() {
+            dict.add("trackers", &self.trackers);
+        }
+        if !self.url_list.is_empty() {
+            dict.add("url-list", &self.url_list);
+        }
+
+        let times = [
+            ("added_time", self.added_time),
+            ("completed_time", self.completed_time),
+            ("last_seen_complete", self.last_seen_complete),
+            ("active_time", self.active_time),
+            ("finished_time", self.finished_time),
+            ("seeding_time", self.seeding_time),
+            ("total_uploaded", self.total_uploaded),
+            ("total_downloaded", self.total_downloaded),
+        ];
+        for (key, value) in times.iter() {
+            if let Some(value) = value {
+                dict.add(*key, *value);
+            }
+        }
+
+        for (key, value) in &self.extra {
+            dict.add(key, Raw(value));
+        }
+    }
+}
+
+/// A value which is bencoded already.
+struct Raw<'a>(&'a [u8]);
+
+impl Encode for Raw<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        enc.write(self.0);
+    }
+}
+
+/// Keys decoded into the typed fields of `FastResume`.
+const TYPED_KEYS: &[&str] = &[
+    "file-format",
+    "file-version",
+    "info-hash",
+    "info-hash2",
+    "name",
+    "save_path",
+    "pieces",
+    "file_priority",
+    "peers",
+    "peers6",
+    "trackers",
+    "url-list",
+    "added_time",
+    "completed_time",
+    "last_seen_complete",
+    "active_time",
+    "finished_time",
+    "seeding_time",
+    "total_uploaded",
+    "total_downloaded",
+];
+
+fn int(value: &Decoder<'_, '_>) -> Result<i64> {
+    value.as_int().ok_or(Error::Invalid {
+        reason: "Expected an integer",
+        pos: value.pos(),
+    })
+}
+
+fn bytes<'a>(value: &Decoder<'a, '_>) -> Result<&'a [u8]> {
+    value.as_bytes().ok_or(Error::Invalid {
+        reason: "Expected a byte string",
+        pos: value.pos(),
+    })
+}
+
+fn string(value: &Decoder<'_, '_>) -> Result<String> {
+    value.as_str().map(String::from).ok_or(Error::Invalid {
+        reason: "Expected a UTF-8 string",
+        pos: value.pos(),
+    })
+}
+
+fn hash<const N: usize>(value: &Decoder<'_, '_>) -> Result<[u8; N]> {
+    let mut hash = [0; N];
+    match bytes(value)? {
+        bytes if bytes.len() == N => hash.copy_from_slice(bytes),
+        _ => {
+            return Err(Error::Invalid {
+                reason: "Invalid info-hash length",
+                pos: value.pos(),
+            })
+        }
+    }
+    Ok(hash)
+}
+
+fn list<'a, 'p>(value: Decoder<'a, 'p>) -> Result<List<'a, 'p>> {
+    let pos = value.pos();
+    value.into_list().ok_or(Error::Invalid {
+        reason: "Expected a list",
+        pos,
+    })
+}
+
+fn strings(value: Decoder<'_, '_>) -> Result<Vec<String>> {
+    list(value)?.iter().map(|v| string(&v)).collect()
+}
+
+fn compact_peers(value: &Decoder<'_, '_>, len: usize, peers: &mut Vec<SocketAddr>) -> Result<()> {
+    let bytes = bytes(value)?;
+    if bytes.len() % len != 0 {
+        return Err(Error::Invalid {
+            reason: "Invalid compact peers length",
+            pos: value.pos(),
+        });
+    }
+    peers.extend(bytes.chunks(len).map(decode_compact_addr));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+
+    fn sample() -> FastResume {
+        let mut resume = FastResume {
+            file_version: Some(FILE_VERSION),
+            info_hash: Some([0xaa; 20]),
+            name: Some("ubuntu.iso".into()),
+            save_path: Some("/srv/downloads".into()),
+            file_priority: vec![4, 0, 7],
+            peers: vec![
+                "10.0.0.1:6881".parse().unwrap(),
+                "[::1]:51413".parse().unwrap(),
+            ],
+            trackers: vec![
+                vec!["http://a/announce".into()],
+                vec!["udp://b:80".into(), "udp://c:80".into()],
+            ],
+            added_time: Some(1_600_000_000),
+            total_uploaded: Some(0),
+            ..FastResume::default()
+        };
+        resume.set_piece(0, true);
+        resume.set_piece(2, true);
+        resume
+    }
+
+    #[test]
+    fn round_trip() {
+        let resume = sample();
+        let buf = resume.encode_to_vec();
+        let parser = &mut Parser::new();
+        let decoded = parser.parse::<FastResume>(&buf).unwrap();
+        assert_eq!(resume, decoded);
+        assert_eq!(buf, decoded.encode_to_vec());
+
+        assert!(decoded.has_piece(0));
+        assert!(!decoded.has_piece(1));
+        assert!(decoded.has_piece(2));
+        assert!(!decoded.has_piece(3));
+    }
+
+    #[test]
+    fn preserves_unknown_keys() {
+        let s = b"d12:auto_managedi1e11:file-format22:libtorrent resume file\
+                  12:file-versioni1e4:name3:foo6:pausedi0e\
+                  10:unfinishedld7:bitmask1:\xf05:piecei3eeee";
+        let parser = &mut Parser::new();
+        let resume = parser.parse::<FastResume>(&s[..]).unwrap();
+        assert_eq!(Some("foo"), resume.name.as_deref());
+        assert_eq!(Some(&b"i1e"[..]), resume.extra("auto_managed"));
+        assert_eq!(
+            vec![&b"auto_managed"[..], b"paused", b"unfinished"],
+            resume.extras().map(|(k, _)| k).collect::<Vec<_>>()
+        );
+        assert_eq!(&s[..], &resume.encode_to_vec()[..]);
+    }
+
+    #[test]
+    fn preserves_file_version() {
+        let s = b"d11:file-format22:libtorrent resume file12:file-versioni2ee";
+        let parser = &mut Parser::new();
+        let resume = parser.parse::<FastResume>(&s[..]).unwrap();
+        assert_eq!(Some(2), resume.file_version);
+        assert_eq!(&s[..], &resume.encode_to_vec()[..]);
+
+        let buf = FastResume::default().encode_to_vec();
+        let resume = parser.parse::<FastResume>(&buf).unwrap();
+        assert_eq!(Some(FILE_VERSION), resume.file_version);
+    }
+
+    #[test]
+    fn preserves_unsorted_unknown_keys() {
+        let s = b"d11:file-format22:libtorrent resume file\
+                  12:file-versioni1e8:settingsd1:bi1e1:ai2eee";
+        let parser = &mut Parser::new();
+        let resume = parser.parse::<FastResume>(&s[..]).unwrap();
+        assert_eq!(Some(&b"d1:bi1e1:ai2ee"[..]), resume.extra("settings"));
+        assert_eq!(&s[..], &resume.encode_to_vec()[..]);
+    }
+
+    #[test]
+    fn set_extra() {
+        let mut resume = sample();
+        resume.set_extra("paused", 1).unwrap();
+        resume.set_extra("auto_managed", 0).unwrap();
+        resume.set_extra("paused", 0).unwrap();
+        assert_eq!(
+            Err(Error::Other("Key belongs to a typed field")),
+            resume.set_extra("name", "bar")
+        );
+        assert_eq!(Some(b"i0e".to_vec()), resume.remove_extra("auto_managed"));
+        assert_eq!(Err(Error::Eof), resume.set_extra("x", Raw(b"")));
+        assert_eq!(
+            Err(Error::Unexpected { pos: 3 }),
+            resume.set_extra("x", Raw(b"i1ei2e"))
+        );
+        assert_eq!(
+            Err(Error::Other("Keys must be sorted")),
+            resume.set_extra("x", Raw(b"d1:bi1e1:ai2ee"))
+        );
+        assert_eq!(None, resume.extra("x"));
+        assert_eq!(None, resume.remove_extra("auto_managed"));
+
+        let buf = resume.encode_to_vec();
+        let parser = &mut Parser::new();
+        let decoded = parser.parse::<FastResume>(&buf).unwrap();
+        assert_eq!(Some(&b"i0e"[..]), decoded.extra("paused"));
+        assert_eq!(resume, decoded);
+    }
+
+    #[test]
+    fn preserves_piece_flags() {
+        let s = b"d11:file-format22:libtorrent resume file6:pieces3:\x03\x00\x02e";
+        let parser = &mut Parser::new();
+        let mut resume = parser.parse::<FastResume>(&s[..]).unwrap();
+        assert!(resume.has_piece(0));
+        assert!(!resume.has_piece(2));
+
+        resume.set_piece(0, false);
+        resume.set_piece(2, true);
+        assert_eq!(vec![0x02, 0x00, 0x03], resume.pieces);
+    }
+
+    #[test]
+    fn missing_file_format() {
+        let parser = &mut Parser::new();
+        let err = parser.parse::<FastResume>(b"d4:name3:fooe").unwrap_err();
+        assert_eq!(Error::missing("file-format", 0), err);
+
+        let err = parser
+            .parse::<FastResume>(b"d11:file-format3:fooe")
+            .unwrap_err();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Not a libtorrent resume file",
+                pos: 17,
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn invalid_field_type() {
+        let s = b"d11:file-format22:libtorrent resume file10:added_time3:nowe";
+        let parser = &mut Parser::new();
+        let err = parser.parse::<FastResume>(&s[..]).unwrap_err();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Expected an integer",
+                pos: 55,
+            },
+            err
+        );
+    }
+}
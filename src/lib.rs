@@ -1,14 +1,72 @@
 //! `ben` is an efficient Bencode parser which parses the structure into
 //! a flat stream of tokens rather than an actual tree and thus avoids
 //! unneccessary allocations.
+//!
+//! # Features
+//!
+//! - `std` (default): implements `std::error::Error` and enables the
+//!   modules which need file system access. Implies `alloc`.
+//! - `alloc`: enables `Parser`, `OwnedDocument`, the `Vec` backed encoder
+//!   and the BitTorrent modules. Without it only `FixedParser`,
+//!   `SliceParser`, the decoder and the encoder builders writing into a
+//!   custom `Sink` are available.
+//! - `arbitrary`: implements `arbitrary::Arbitrary` for `value::Value`,
+//!   generating valid bencode documents for fuzzing.
+//! - `bytes`: implements `encode::Sink` for `bytes::BytesMut`.
+//! - `mmap`: adds `mmap::ParsedFile` for parsing memory-mapped files.
+//! - `parallel`: adds `pool::ParserPool::parse_batch` which parses many
+//!   buffers in parallel using rayon.
+//! - `proptest`: adds the `proptest` module with strategies generating
+//!   canonical bencode documents and round trip checks.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod builder;
 pub mod decode;
+#[cfg(feature = "alloc")]
+mod document;
 pub mod encode;
 mod error;
+#[cfg(feature = "alloc")]
+pub mod extension;
+#[cfg(feature = "alloc")]
+pub mod fastresume;
+#[cfg(feature = "alloc")]
+pub mod magnet;
+#[cfg(feature = "alloc")]
+pub mod merkle;
+#[cfg(feature = "alloc")]
+pub mod metainfo;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 mod parse;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+mod scan;
+#[cfg(feature = "alloc")]
+pub mod schema;
+#[cfg(feature = "alloc")]
+pub mod tape;
 mod token;
+#[cfg(feature = "alloc")]
+pub mod tracker;
+#[cfg(feature = "alloc")]
+pub mod ut_metadata;
+#[cfg(feature = "alloc")]
+pub mod value;
 
 pub use decode::{Decode, Decoder};
+#[cfg(feature = "alloc")]
+pub use document::OwnedDocument;
 pub use encode::{Encode, Encoder};
 pub use error::{Error, Result};
+#[cfg(feature = "alloc")]
 pub use parse::Parser;
+pub use parse::{FixedParser, SliceParser};
+pub use token::{Token, TokenKind};
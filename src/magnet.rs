@@ -0,0 +1,520 @@
+//! Magnet URIs (BEP 9 and BEP 53).
+//!
+//! A `Magnet` can be parsed from a `magnet:?` URI or built from a decoded
+//! metainfo document, and is formatted back to a URI with `Display`.
+
+use crate::error::{Error, Result};
+use crate::metainfo::Metainfo;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::RangeInclusive;
+use core::str::FromStr;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+const PREFIX: &str = "magnet:?";
+const BTIH: &str = "urn:btih:";
+const BTMH: &str = "urn:btmh:";
+
+/// Multihash prefix of a SHA-256 digest: function code 0x12, length 0x20.
+const SHA256_MULTIHASH: [u8; 2] = [0x12, 0x20];
+
+/// A parsed magnet link.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Magnet {
+    /// v1 info-hash from `xt=urn:btih:`.
+    pub info_hash: Option<[u8; 20]>,
+
+    /// v2 info-hash from `xt=urn:btmh:`.
+    pub info_hash_v2: Option<[u8; 32]>,
+
+    /// Display name (`dn`).
+    pub display_name: Option<String>,
+
+    /// Tracker URLs (`tr`).
+    pub trackers: Vec<String>,
+
+    /// Web seed URLs (`ws`).
+    pub web_seeds: Vec<String>,
+
+    /// Peer addresses (`x.pe`) as `host:port`.
+    pub peers: Vec<String>,
+
+    /// Indices of the files to download (`so`), as inclusive ranges.
+    pub select_only: Vec<RangeInclusive<usize>>,
+}
+
+impl Magnet {
+    /// Parse a magnet URI.
+    ///
+    /// At least one of `urn:btih:` or `urn:btmh:` must be present. Unknown
+    /// parameters are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::magnet::Magnet;
+    ///
+    /// let magnet = Magnet::parse(
+    ///     "magnet:?xt=urn:btih:c9e15763f722f23e98a29decdfae341b98d53056&dn=Hello%20World",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(Some("Hello World"), magnet.display_name.as_deref());
+    /// ```
+    pub fn parse(uri: &str) -> Result<Self> {
+        let rest = match strip_prefix_ignore_case(uri, PREFIX) {
+            Some(rest) => rest,
+            None => {
+                return Err(Error::Invalid {
+                    reason: "Not a magnet URI",
+                    pos: 0,
+                })
+            }
+        };
+
+        let mut magnet = Magnet::default();
+        let mut pos = PREFIX.len();
+        for param in rest.split('&') {
+            let start = pos;
+            pos += param.len() + 1;
+            if param.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match param.find('=') {
+                Some(i) => (&param[..i], &param[i + 1..]),
+                None => continue,
+            };
+            let value_pos = start + key.len() + 1;
+            let value = percent_decode(value, value_pos)?;
+
+            match key {
+                "xt" => magnet.parse_exact_topic(&value, value_pos)?,
+                "dn" => magnet.display_name = Some(value),
+                "ws" => magnet.web_seeds.push(value),
+                "x.pe" => magnet.peers.push(value),
+                "so" => magnet.select_only = parse_select_only(&value, value_pos)?,
+                // Some clients number repeated keys, e.g. `tr.1`
+                _ if key == "tr" || key.starts_with("tr.") => magnet.trackers.push(value),
+                _ => {}
+            }
+        }
+
+        if magnet.info_hash.is_none() && magnet.info_hash_v2.is_none() {
+            return Err(Error::Invalid {
+                reason: "Magnet URI has no info-hash",
+                pos: 0,
+            });
+        }
+        Ok(magnet)
+    }
+
+    /// Create a magnet link for a decoded metainfo document.
+    ///
+    /// The info-hashes are computed over the raw bytes of the `info`
+    /// dictionary; a v1 hash for torrents with `pieces` and a v2 hash for
+    /// torrents with a `file tree`. Trackers are taken from `announce-list`,
+    /// or `announce` if there is no list, and web seeds from `url-list`.
+    pub fn from_metainfo(metainfo: &Metainfo<'_, '_>) -> Result<Self> {
+        let info = metainfo.info()?;
+        let raw = info.as_dict().as_raw_bytes();
+
+        let mut magnet = Magnet::default();
+        if info.is_v1() {
+            magnet.info_hash = Some(Sha1::digest(raw).into());
+        }
+        if info.is_v2() {
+            magnet.info_hash_v2 = Some(Sha256::digest(raw).into());
+        }
+        if magnet.info_hash.is_none() && magnet.info_hash_v2.is_none() {
+            return Err(Error::missing("pieces", info.as_dict().pos()));
+        }
+        magnet.display_name = info.name().map(String::from);
+
+        let dict = metainfo.as_dict();
+        if let Some(tiers) = dict.get_list("announce-list") {
+            for tier in tiers.iter().filter_map(|tier| tier.into_list()) {
+                for url in tier.iter().filter_map(|url| url.as_str()) {
+                    if !magnet.trackers.iter().any(|t| t == url) {
+                        magnet.trackers.push(url.to_owned());
+                    }
+                }
+            }
+        } else if let Some(url) = dict.get_str("announce") {
+            magnet.trackers.push(url.to_owned());
+        }
+
+        if let Some(urls) = dict.get_list("url-list") {
+            let urls = urls.iter().filter_map(|url| url.as_str());
+            magnet.web_seeds.extend(urls.map(String::from));
+        } else if let Some(url) = dict.get_str("url-list") {
+            magnet.web_seeds.push(url.to_owned());
+        }
+
+        Ok(magnet)
+    }
+
+    fn parse_exact_topic(&mut self, value: &str, pos: usize) -> Result<()> {
+        if let Some(hash) = strip_prefix_ignore_case(value, BTIH) {
+            let pos = pos + BTIH.len();
+            let mut info_hash = [0; 20];
+            let ok = match hash.len() {
+                40 => hex_decode(hash, &mut info_hash),
+                32 => base32_decode(hash, &mut info_hash),
+                _ => false,
+            };
+            if !ok {
+                return Err(Error::Invalid {
+                    reason: "Invalid btih info-hash",
+                    pos,
+                });
+            }
+            self.info_hash = Some(info_hash);
+        } else if let Some(hash) = strip_prefix_ignore_case(value, BTMH) {
+            let pos = pos + BTMH.len();
+            let mut multihash = [0; 34];
+            if hash.len() != 68
+                || !hex_decode(hash, &mut multihash)
+                || multihash[..2] != SHA256_MULTIHASH
+            {
+                return Err(Error::Invalid {
+                    reason: "Invalid btmh info-hash",
+                    pos,
+                });
+            }
+            let mut info_hash = [0; 32];
+            info_hash.copy_from_slice(&multihash[2..]);
+            self.info_hash_v2 = Some(info_hash);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Magnet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Magnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PREFIX)?;
+
+        let mut sep = "";
+        if let Some(hash) = &self.info_hash {
+            write!(f, "xt={}", BTIH)?;
+            hex_encode(hash, f)?;
+            sep = "&";
+        }
+        if let Some(hash) = &self.info_hash_v2 {
+            write!(f, "{}xt={}", sep, BTMH)?;
+            hex_encode(&SHA256_MULTIHASH, f)?;
+            hex_encode(hash, f)?;
+            sep = "&";
+        }
+        if let Some(name) = &self.display_name {
+            write!(f, "{}dn=", sep)?;
+            percent_encode(name, f)?;
+            sep = "&";
+        }
+        for (key, values) in &[
+            ("tr", &self.trackers),
+            ("ws", &self.web_seeds),
+            ("x.pe", &self.peers),
+        ] {
+            for value in values.iter() {
+                write!(f, "{}{}=", sep, key)?;
+                percent_encode(value, f)?;
+                sep = "&";
+            }
+        }
+        if !self.select_only.is_empty() {
+            write!(f, "{}so=", sep)?;
+            for (i, range) in self.select_only.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                if range.start() == range.end() {
+                    write!(f, "{}", range.start())?;
+                } else {
+                    write!(f, "{}-{}", range.start(), range.end())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(head) if head.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+fn parse_select_only(value: &str, pos: usize) -> Result<Vec<RangeInclusive<usize>>> {
+    let invalid = Error::Invalid {
+        reason: "Invalid select-only list",
+        pos,
+    };
+    value
+        .split(',')
+        .map(|item| {
+            let (start, end) = match item.find('-') {
+                Some(i) => (&item[..i], &item[i + 1..]),
+                None => (item, item),
+            };
+            match (start.parse(), end.parse()) {
+                (Ok(start), Ok(end)) if start <= end => Ok(start..=end),
+                _ => Err(invalid.clone()),
+            }
+        })
+        .collect()
+}
+
+fn hex_encode(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for b in bytes {
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+fn hex_decode(s: &str, out: &mut [u8]) -> bool {
+    if s.len() != out.len() * 2 {
+        return false;
+    }
+    for (i, pair) in s.as_bytes().chunks(2).enumerate() {
+        match (hex_digit(pair[0]), hex_digit(pair[1])) {
+            (Some(hi), Some(lo)) => out[i] = hi << 4 | lo,
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes unpadded RFC 4648 base32.
+fn base32_decode(s: &str, out: &mut [u8]) -> bool {
+    if s.len() * 5 != out.len() * 8 {
+        return false;
+    }
+    let mut bits: u64 = 0;
+    let mut nbits = 0;
+    let mut i = 0;
+    for c in s.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return false,
+        };
+        bits = bits << 5 | u64::from(v);
+        nbits += 5;
+        if nbits >= 8 {
+            nbits -= 8;
+            out[i] = (bits >> nbits) as u8;
+            i += 1;
+        }
+    }
+    true
+}
+
+fn percent_encode(s: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for &b in s.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                f.write_char(char::from(b))?
+            }
+            _ => write!(f, "%{:02X}", b)?,
+        }
+    }
+    Ok(())
+}
+
+fn percent_decode(s: &str, pos: usize) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hi = bytes.get(i + 1).copied().and_then(hex_digit);
+                let lo = bytes.get(i + 2).copied().and_then(hex_digit);
+                match (hi, lo) {
+                    (Some(hi), Some(lo)) => out.push(hi << 4 | lo),
+                    _ => return Err(Error::Unexpected { pos: pos + i }),
+                }
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| Error::Invalid {
+        reason: "Parameter is not valid UTF-8",
+        pos,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+    use crate::Parser;
+
+    const HASH: &str = "c9e15763f722f23e98a29decdfae341b98d53056";
+
+    #[test]
+    fn parse() {
+        let uri = format!(
+            "magnet:?xt=urn:btih:{}&dn=Big+Buck%20Bunny&tr=udp%3A%2F%2Fa%3A80&tr.1=http://b/announce\
+             &x.pe=10.0.0.1:6881&x.pe=[::1]:6881&so=0,2,4-6&unknown=1",
+            HASH
+        );
+        let magnet = Magnet::parse(&uri).unwrap();
+        assert_eq!(
+            Magnet {
+                info_hash: Some([
+                    0xc9, 0xe1, 0x57, 0x63, 0xf7, 0x22, 0xf2, 0x3e, 0x98, 0xa2, 0x9d, 0xec, 0xdf,
+                    0xae, 0x34, 0x1b, 0x98, 0xd5, 0x30, 0x56
+                ]),
+                info_hash_v2: None,
+                display_name: Some("Big Buck Bunny".into()),
+                trackers: vec!["udp://a:80".into(), "http://b/announce".into()],
+                web_seeds: vec![],
+                peers: vec!["10.0.0.1:6881".into(), "[::1]:6881".into()],
+                select_only: vec![0..=0, 2..=2, 4..=6],
+            },
+            magnet
+        );
+    }
+
+    #[test]
+    fn parse_base32() {
+        let hex = Magnet::parse(&format!("magnet:?xt=urn:btih:{}", HASH)).unwrap();
+        let base32 = Magnet::parse("magnet:?xt=urn:btih:ZHQVOY7XELZD5GFCTXWN7LRUDOMNKMCW").unwrap();
+        assert_eq!(hex, base32);
+    }
+
+    #[test]
+    fn parse_btmh() {
+        let uri = format!("magnet:?xt=urn:btmh:1220{}", "ab".repeat(32));
+        let magnet = Magnet::parse(&uri).unwrap();
+        assert_eq!(None, magnet.info_hash);
+        assert_eq!(Some([0xab; 32]), magnet.info_hash_v2);
+
+        let uri = format!("magnet:?xt=urn:btmh:1120{}", "ab".repeat(32));
+        assert_eq!(
+            Error::Invalid {
+                reason: "Invalid btmh info-hash",
+                pos: 20,
+            },
+            Magnet::parse(&uri).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Error::Invalid {
+                reason: "Not a magnet URI",
+                pos: 0,
+            },
+            Magnet::parse("http://example.com").unwrap_err()
+        );
+        assert!(Magnet::parse("magnét:?").is_err());
+        assert_eq!(
+            Error::Invalid {
+                reason: "Magnet URI has no info-hash",
+                pos: 0,
+            },
+            Magnet::parse("magnet:?dn=x").unwrap_err()
+        );
+        assert_eq!(
+            Error::Invalid {
+                reason: "Invalid btih info-hash",
+                pos: 20,
+            },
+            Magnet::parse("magnet:?xt=urn:btih:xyz").unwrap_err()
+        );
+        assert_eq!(
+            Error::Unexpected { pos: 12 },
+            Magnet::parse("magnet:?dn=a%zz").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let magnet = Magnet {
+            info_hash: Some([1; 20]),
+            info_hash_v2: Some([2; 32]),
+            display_name: Some("a b&c".into()),
+            trackers: vec!["http://t/a?x=1".into()],
+            web_seeds: vec!["http://w/".into()],
+            peers: vec!["1.2.3.4:5".into()],
+            select_only: vec![1..=1, 3..=5],
+        };
+        let uri = magnet.to_string();
+        assert_eq!(
+            format!(
+                "magnet:?xt=urn:btih:{}&xt=urn:btmh:1220{}&dn=a%20b%26c&tr=http%3A%2F%2Ft%2Fa%3Fx%3D1\
+                 &ws=http%3A%2F%2Fw%2F&x.pe=1.2.3.4%3A5&so=1,3-5",
+                "01".repeat(20),
+                "02".repeat(32)
+            ),
+            uri
+        );
+        assert_eq!(magnet, uri.parse().unwrap());
+    }
+
+    #[test]
+    fn from_metainfo() {
+        let mut v = vec![];
+        let mut root = v.add_dict();
+        root.add("announce", "http://a");
+        root.add(
+            "announce-list",
+            vec![vec!["http://a", "http://b"], vec!["http://a"]],
+        );
+        {
+            let mut info = root.add_dict("info");
+            info.add("length", 1);
+            info.add("name", "file");
+            info.add("piece length", 16384);
+            info.add("pieces", [0u8; 20]);
+        }
+        root.add("url-list", "http://seed");
+        root.finish();
+
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&v).unwrap();
+        let magnet = Magnet::from_metainfo(&metainfo).unwrap();
+
+        let info = metainfo.info().unwrap();
+        let info_hash: [u8; 20] = Sha1::digest(info.as_dict().as_raw_bytes()).into();
+        assert_eq!(Some(info_hash), magnet.info_hash);
+        assert_eq!(None, magnet.info_hash_v2);
+        assert_eq!(Some("file"), magnet.display_name.as_deref());
+        assert_eq!(vec!["http://a", "http://b"], magnet.trackers);
+        assert_eq!(vec!["http://seed"], magnet.web_seeds);
+        assert_eq!(magnet, Magnet::parse(&magnet.to_string()).unwrap());
+    }
+}
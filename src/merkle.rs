@@ -0,0 +1,314 @@
+//! SHA-256 merkle hash trees as used by BitTorrent v2 (BEP 52).
+//!
+//! Every file is split into 16 KiB blocks which form the leaves of a binary
+//! tree. The number of leaves is padded to a power of two with zero hashes.
+//! The layer whose nodes each cover one piece is the file's piece layer and
+//! the top of the tree is its `pieces root`.
+
+use crate::metainfo::{FileEntry, PieceLayers, BLOCK_SIZE, V2_HASH_LEN};
+#[cfg(feature = "std")]
+use alloc::vec;
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+/// A SHA-256 hash.
+pub type Hash = [u8; V2_HASH_LEN];
+
+/// Merkle hashes of a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHashes {
+    /// File size in bytes.
+    pub length: u64,
+
+    /// Root of the file's merkle tree. `None` for empty files.
+    pub pieces_root: Option<Hash>,
+
+    /// Concatenated piece layer hashes. Empty unless the file is larger than
+    /// one piece, as such files don't get a `piece layers` entry.
+    pub piece_layer: Vec<u8>,
+}
+
+/// Computes the merkle root and piece layer of a file.
+///
+/// `piece_length` must be a power of two of at least 16 KiB.
+#[cfg(feature = "std")]
+pub fn hash_file<R: Read>(mut reader: R, piece_length: u64) -> io::Result<FileHashes> {
+    let blocks_per_piece = blocks_per_piece(piece_length).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "piece length must be a power of two of at least 16 KiB",
+        )
+    })?;
+
+    let mut leaves = vec![];
+    let mut length = 0;
+    let mut block = vec![0; BLOCK_SIZE];
+    loop {
+        let n = read_block(&mut reader, &mut block)?;
+        if n == 0 {
+            break;
+        }
+        length += n as u64;
+        leaves.push(Sha256::digest(&block[..n]).into());
+        if n < BLOCK_SIZE {
+            break;
+        }
+    }
+
+    if leaves.is_empty() {
+        return Ok(FileHashes {
+            length,
+            pieces_root: None,
+            piece_layer: vec![],
+        });
+    }
+
+    if length <= piece_length {
+        return Ok(FileHashes {
+            length,
+            pieces_root: Some(root(leaves, [0; V2_HASH_LEN])),
+            piece_layer: vec![],
+        });
+    }
+
+    let layer: Vec<Hash> = leaves
+        .chunks(blocks_per_piece)
+        .map(|piece| {
+            let mut piece = piece.to_vec();
+            piece.resize(blocks_per_piece, [0; V2_HASH_LEN]);
+            root(piece, [0; V2_HASH_LEN])
+        })
+        .collect();
+    let piece_layer = layer.concat();
+    let pieces_root = root(layer, pad_hash(blocks_per_piece));
+
+    Ok(FileHashes {
+        length,
+        pieces_root: Some(pieces_root),
+        piece_layer,
+    })
+}
+
+/// Computes the merkle root from a file's piece layer.
+///
+/// Returns `None` if the layer is empty, is not a multiple of 32 bytes or
+/// `piece_length` is not a power of two of at least 16 KiB.
+pub fn root_from_piece_layer(layer: &[u8], piece_length: u64) -> Option<Hash> {
+    let blocks_per_piece = blocks_per_piece(piece_length)?;
+    if layer.is_empty() || layer.len() % V2_HASH_LEN != 0 {
+        return None;
+    }
+
+    let hashes = layer
+        .chunks(V2_HASH_LEN)
+        .map(|h| {
+            let mut hash = [0; V2_HASH_LEN];
+            hash.copy_from_slice(h);
+            hash
+        })
+        .collect();
+    Some(root(hashes, pad_hash(blocks_per_piece)))
+}
+
+/// Returns true if the piece layer hashes up to the given pieces root.
+pub fn verify_piece_layer(layer: &[u8], pieces_root: &[u8], piece_length: u64) -> bool {
+    match root_from_piece_layer(layer, piece_length) {
+        Some(root) => root[..] == *pieces_root,
+        None => false,
+    }
+}
+
+/// Verifies the `piece layers` entry of a file from a parsed file tree.
+///
+/// Files no larger than one piece have no piece layer and always pass. Larger
+/// files fail if their entry is missing, has the wrong number of hashes or
+/// doesn't hash up to the file's `pieces root`.
+pub fn verify_file(layers: &PieceLayers<'_, '_>, file: &FileEntry<'_>, piece_length: u64) -> bool {
+    if file.length <= piece_length {
+        return true;
+    }
+
+    let root = match file.pieces_root {
+        Some(root) => root,
+        None => return false,
+    };
+    let layer = match layers.get(root) {
+        Some(layer) => layer,
+        None => return false,
+    };
+
+    let pieces = file.length.div_ceil(piece_length);
+    layer.len() as u64 == pieces * V2_HASH_LEN as u64
+        && verify_piece_layer(layer, root, piece_length)
+}
+
+fn blocks_per_piece(piece_length: u64) -> Option<usize> {
+    if piece_length < BLOCK_SIZE as u64 || !piece_length.is_power_of_two() {
+        return None;
+    }
+    Some((piece_length / BLOCK_SIZE as u64) as usize)
+}
+
+/// Returns the root of a subtree with given number of zero leaves.
+fn pad_hash(leaves: usize) -> Hash {
+    debug_assert!(leaves.is_power_of_two());
+
+    let mut hash = [0; V2_HASH_LEN];
+    let mut n = 1;
+    while n < leaves {
+        hash = hash_pair(&hash, &hash);
+        n *= 2;
+    }
+    hash
+}
+
+/// Reduces a layer to its root, padding every layer to an even number of
+/// nodes with `pad`, the root of an all-zero subtree of the same height.
+fn root(mut layer: Vec<Hash>, mut pad: Hash) -> Hash {
+    debug_assert!(!layer.is_empty());
+
+    while layer.len() > 1 {
+        if layer.len() % 2 == 1 {
+            layer.push(pad);
+        }
+        for i in 0..layer.len() / 2 {
+            layer[i] = hash_pair(&layer[2 * i], &layer[2 * i + 1]);
+        }
+        layer.truncate(layer.len() / 2);
+        pad = hash_pair(&pad, &pad);
+    }
+    layer[0]
+}
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Reads until the buffer is full or the reader is exhausted.
+#[cfg(feature = "std")]
+fn read_block<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+    use crate::metainfo::FileTree;
+    use crate::Parser;
+
+    fn sha256(data: &[u8]) -> Hash {
+        Sha256::digest(data).into()
+    }
+
+    #[test]
+    fn empty_file() {
+        let hashes = hash_file(&[][..], 16384).unwrap();
+        assert_eq!(0, hashes.length);
+        assert_eq!(None, hashes.pieces_root);
+        assert!(hashes.piece_layer.is_empty());
+    }
+
+    #[test]
+    fn single_block() {
+        let data = vec![1; 100];
+        let hashes = hash_file(&data[..], 16384).unwrap();
+        assert_eq!(100, hashes.length);
+        assert_eq!(Some(sha256(&data)), hashes.pieces_root);
+        assert!(hashes.piece_layer.is_empty());
+    }
+
+    #[test]
+    fn single_piece_padded_to_power_of_two() {
+        // 3 blocks are padded to 4 leaves
+        let data = vec![1; 2 * BLOCK_SIZE + 1];
+        let hashes = hash_file(&data[..], 65536).unwrap();
+
+        let a = sha256(&data[..BLOCK_SIZE]);
+        let b = sha256(&data[BLOCK_SIZE..2 * BLOCK_SIZE]);
+        let c = sha256(&data[2 * BLOCK_SIZE..]);
+        let expected = hash_pair(&hash_pair(&a, &b), &hash_pair(&c, &[0; 32]));
+        assert_eq!(Some(expected), hashes.pieces_root);
+        assert!(hashes.piece_layer.is_empty());
+    }
+
+    #[test]
+    fn piece_layer() {
+        // 3 pieces of 2 blocks each, the last one being a partial block
+        let data = vec![7; 4 * BLOCK_SIZE + 10];
+        let hashes = hash_file(&data[..], 32768).unwrap();
+        assert_eq!(3 * 32, hashes.piece_layer.len());
+
+        let block = sha256(&[7; BLOCK_SIZE]);
+        let full = hash_pair(&block, &block);
+        let last = hash_pair(&sha256(&[7; 10]), &[0; 32]);
+        let pad = hash_pair(&[0; 32], &[0; 32]);
+        assert_eq!([full, full, last].concat(), hashes.piece_layer);
+
+        let expected = hash_pair(&hash_pair(&full, &full), &hash_pair(&last, &pad));
+        assert_eq!(Some(expected), hashes.pieces_root);
+
+        // Same root as hashing the leaves directly
+        let leaves = vec![block, block, block, block, sha256(&[7; 10])];
+        assert_eq!(expected, root(leaves, [0; 32]));
+
+        assert!(verify_piece_layer(&hashes.piece_layer, &expected, 32768));
+        assert!(!verify_piece_layer(
+            &hashes.piece_layer[32..],
+            &expected,
+            32768
+        ));
+    }
+
+    #[test]
+    fn invalid_piece_length() {
+        let err = hash_file(&[][..], 1000).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(None, root_from_piece_layer(&[0; 32], 8192));
+    }
+
+    #[test]
+    fn verify_decoded_file() {
+        let data = vec![3; 3 * BLOCK_SIZE];
+        let hashes = hash_file(&data[..], 16384).unwrap();
+        let root = hashes.pieces_root.unwrap();
+
+        let mut v = vec![];
+        let mut doc = v.add_list();
+        {
+            let mut tree = doc.add_dict();
+            let mut node = tree.add_dict("a");
+            let mut file = node.add_dict("");
+            file.add("length", data.len() as i64);
+            file.add("pieces root", root);
+        }
+        {
+            let mut layers = doc.add_dict();
+            layers.add(root, &hashes.piece_layer[..]);
+        }
+        doc.finish();
+
+        let parser = &mut Parser::new();
+        let doc = parser.parse::<crate::decode::List>(&v).unwrap();
+        let tree: FileTree = crate::Decode::decode(doc.get(0).unwrap()).unwrap();
+        let layers: PieceLayers = crate::Decode::decode(doc.get(1).unwrap()).unwrap();
+
+        let file = tree.iter().next().unwrap().unwrap();
+        assert!(verify_file(&layers, &file, 16384));
+        assert!(!verify_file(&layers, &file, 32768));
+    }
+}
@@ -0,0 +1,829 @@
+//! Typed access to BitTorrent metainfo (`.torrent`) documents.
+//!
+//! This covers both the v1 format (BEP 3) and the v2 format (BEP 52) as well
+//! as hybrid torrents which carry both descriptions of the same content.
+//! Everything here is a thin view over `decode::Dict`, so no data is copied
+//! out of the source buffer.
+
+use crate::decode::{Decode, Decoder, Dict, DictIter, List, ListIter};
+use crate::error::{Error, Result};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Size of the blocks the v2 merkle trees are built from.
+pub const BLOCK_SIZE: usize = 16 * 1024;
+
+/// Length of a SHA-1 piece hash in the v1 `pieces` field.
+pub const V1_HASH_LEN: usize = 20;
+
+/// Length of a SHA-256 hash in the v2 `pieces root` and `piece layers` fields.
+pub const V2_HASH_LEN: usize = 32;
+
+/// A complete metainfo document.
+#[derive(Debug)]
+pub struct Metainfo<'a, 'p> {
+    dict: Dict<'a, 'p>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for Metainfo<'a, 'p> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let dict = Dict::decode(decoder)?;
+        Ok(Self { dict })
+    }
+}
+
+impl<'a, 'p> Metainfo<'a, 'p> {
+    /// Returns the underlying dictionary.
+    pub fn as_dict(&self) -> &Dict<'a, 'p> {
+        &self.dict
+    }
+
+    /// Returns the `info` dictionary.
+    pub fn info(&self) -> Result<Info<'a, 'p>> {
+        match self.dict.get("info") {
+            Some(info) => Info::decode(info),
+            None => Err(Error::missing("info", self.dict.pos())),
+        }
+    }
+
+    /// Returns the `piece layers` dictionary of a v2 torrent, if present.
+    pub fn piece_layers(&self) -> Option<PieceLayers<'a, 'p>> {
+        let dict = self.dict.get_dict("piece layers")?;
+        Some(PieceLayers { dict })
+    }
+
+    /// Validates the `info` dictionary and, for v2 torrents, that the
+    /// `piece layers` has a correctly sized entry for every file larger than
+    /// one piece.
+    ///
+    /// This checks structure only, no hashes are verified.
+    pub fn validate(&self) -> Result<()> {
+        let info = self.info()?;
+        info.validate()?;
+
+        let file_tree = match info.file_tree() {
+            Some(file_tree) => file_tree,
+            None => return Ok(()),
+        };
+
+        let piece_length = info.piece_length()? as u64;
+        let layers = self.piece_layers();
+        for file in file_tree.iter() {
+            let file = file?;
+            if file.length <= piece_length {
+                continue;
+            }
+
+            // Checked by `FileTree::iter` for non-empty files
+            let root = file.pieces_root.unwrap_or_default();
+            let layer = layers
+                .as_ref()
+                .and_then(|layers| layers.get(root))
+                .ok_or_else(|| Error::missing("piece layers", self.dict.pos()))?;
+
+            let pieces = file.length.div_ceil(piece_length);
+            if layer.len() as u64 != pieces * V2_HASH_LEN as u64 {
+                return Err(Error::Other("Piece layer has wrong length"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `info` dictionary of a metainfo document.
+#[derive(Debug)]
+pub struct Info<'a, 'p> {
+    dict: Dict<'a, 'p>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for Info<'a, 'p> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let dict = Dict::decode(decoder)?;
+        Ok(Self { dict })
+    }
+}
+
+impl<'a, 'p> Info<'a, 'p> {
+    /// Returns the underlying dictionary.
+    pub fn as_dict(&self) -> &Dict<'a, 'p> {
+        &self.dict
+    }
+
+    /// Returns the suggested name of the file or directory.
+    pub fn name(&self) -> Option<&'a str> {
+        self.dict.get_str("name")
+    }
+
+    /// Returns the `meta version` field. Absent for v1-only torrents.
+    pub fn meta_version(&self) -> Option<i64> {
+        self.dict.get_int("meta version")
+    }
+
+    /// Returns the number of bytes in each piece.
+    pub fn piece_length(&self) -> Result<i64> {
+        let pos = self.dict.pos();
+        let piece_length = self
+            .dict
+            .get_int("piece length")
+            .ok_or_else(|| Error::missing("piece length", pos))?;
+        if piece_length <= 0 {
+            return Err(Error::Invalid {
+                reason: "Piece length must be positive",
+                pos,
+            });
+        }
+        Ok(piece_length)
+    }
+
+    /// Returns the concatenated SHA-1 hashes of a v1 torrent.
+    pub fn pieces(&self) -> Option<&'a [u8]> {
+        self.dict.get_bytes("pieces")
+    }
+
+    /// Returns the v2 file tree.
+    pub fn file_tree(&self) -> Option<FileTree<'a, 'p>> {
+        let dict = self.dict.get_dict("file tree")?;
+        Some(FileTree { dict })
+    }
+
+    /// Returns true if this info dictionary describes the content in the v1
+    /// format.
+    pub fn is_v1(&self) -> bool {
+        self.pieces().is_some()
+    }
+
+    /// Returns true if this info dictionary describes the content in the v2
+    /// format.
+    pub fn is_v2(&self) -> bool {
+        self.meta_version() == Some(2) && self.file_tree().is_some()
+    }
+
+    /// Returns true if this is a hybrid torrent, i.e. both a v1 and a v2
+    /// torrent.
+    pub fn is_hybrid(&self) -> bool {
+        self.is_v1() && self.is_v2()
+    }
+
+    /// Returns an iterator over the files of the v1 description.
+    ///
+    /// Single-file torrents yield one entry with an empty path, the file name
+    /// being the torrent name.
+    pub fn v1_files(&self) -> Result<V1Files<'a, 'p>> {
+        let pos = self.dict.pos();
+        if let Some(files) = self.dict.get("files") {
+            let files = List::decode(files)?;
+            Ok(V1Files {
+                inner: V1FilesInner::Multi(files.iter()),
+            })
+        } else if let Some(length) = self.dict.get_int("length") {
+            Ok(V1Files {
+                inner: V1FilesInner::Single(Some(length)),
+            })
+        } else {
+            Err(Error::missing("files", pos))
+        }
+    }
+
+    /// Validates the structure of this info dictionary.
+    ///
+    /// For hybrid torrents this also checks that the v1 file list (ignoring
+    /// padding files) describes the same files in the same order as the v2
+    /// file tree, and that the v1 piece count covers the padded content.
+    pub fn validate(&self) -> Result<()> {
+        let pos = self.dict.pos();
+        let piece_length = self.piece_length()?;
+
+        if self.name().is_none() {
+            return Err(Error::missing("name", pos));
+        }
+
+        if !self.is_v1() && !self.is_v2() {
+            if self.meta_version().is_some() {
+                return Err(Error::missing("file tree", pos));
+            }
+            return Err(Error::missing("pieces", pos));
+        }
+
+        if let Some(pieces) = self.pieces() {
+            if pieces.len() % V1_HASH_LEN != 0 {
+                return Err(Error::Invalid {
+                    reason: "Pieces length is not a multiple of 20",
+                    pos,
+                });
+            }
+
+            let mut total: u64 = 0;
+            for file in self.v1_files()? {
+                total = total.checked_add(file?.length).ok_or(Error::Invalid {
+                    reason: "Total length overflows",
+                    pos,
+                })?;
+            }
+
+            let piece_length = piece_length as u64;
+            let expected = total.div_ceil(piece_length);
+            if (pieces.len() / V1_HASH_LEN) as u64 != expected {
+                return Err(Error::Invalid {
+                    reason: "Piece count does not match total length",
+                    pos,
+                });
+            }
+        }
+
+        if let Some(file_tree) = self.file_tree() {
+            if self.meta_version() != Some(2) {
+                return Err(Error::Invalid {
+                    reason: "Unsupported meta version",
+                    pos,
+                });
+            }
+
+            if piece_length < BLOCK_SIZE as i64 || !(piece_length as u64).is_power_of_two() {
+                return Err(Error::Invalid {
+                    reason: "Piece length must be a power of two of at least 16 KiB",
+                    pos,
+                });
+            }
+
+            for file in file_tree.iter() {
+                file?;
+            }
+        }
+
+        if self.is_hybrid() {
+            self.validate_hybrid()?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_hybrid(&self) -> Result<()> {
+        let pos = self.dict.pos();
+        let mismatch = Error::Invalid {
+            reason: "v1 and v2 file lists differ",
+            pos,
+        };
+
+        let single = self.dict.get("files").is_none();
+        let mut v1 = self.v1_files()?;
+        let mut v2 = self
+            .file_tree()
+            .ok_or_else(|| Error::missing("file tree", pos))?
+            .iter();
+        loop {
+            let v1_file = loop {
+                match v1.next().transpose()? {
+                    Some(file) if file.is_padding() => continue,
+                    file => break file,
+                }
+            };
+            let v2_file = v2.next().transpose()?;
+
+            match (v1_file, v2_file) {
+                (None, None) => return Ok(()),
+                (Some(a), Some(b)) => {
+                    // A single-file v1 torrent has no path, so its name
+                    // stands in for the only file tree entry.
+                    let same_path = if single {
+                        b.path.len() == 1 && Some(b.path[0]) == self.name()
+                    } else {
+                        a.path == b.path
+                    };
+                    if !same_path || a.length != b.length {
+                        return Err(mismatch);
+                    }
+                }
+                _ => return Err(mismatch),
+            }
+        }
+    }
+}
+
+/// A file of the v1 description of a torrent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct V1File<'a> {
+    /// Path components relative to the torrent directory. Empty for
+    /// single-file torrents.
+    pub path: Vec<&'a str>,
+
+    /// File size in bytes.
+    pub length: u64,
+
+    /// BEP 47 file attributes, e.g. `p` for padding files.
+    pub attr: &'a [u8],
+}
+
+impl V1File<'_> {
+    /// Returns true if this is a BEP 47 padding file.
+    pub fn is_padding(&self) -> bool {
+        self.attr.contains(&b'p')
+    }
+}
+
+/// Iterator over the files of a v1 info dictionary.
+pub struct V1Files<'a, 'p> {
+    inner: V1FilesInner<'a, 'p>,
+}
+
+enum V1FilesInner<'a, 'p> {
+    Single(Option<i64>),
+    Multi(ListIter<'a, 'p>),
+}
+
+impl<'a, 'p> Iterator for V1Files<'a, 'p> {
+    type Item = Result<V1File<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            V1FilesInner::Single(file) => {
+                let length = file.take()?;
+                Some(length_from_int(length, 0).map(|length| V1File {
+                    path: vec![],
+                    length,
+                    attr: &[],
+                }))
+            }
+            V1FilesInner::Multi(iter) => {
+                let file = iter.next()?;
+                Some(decode_v1_file(file))
+            }
+        }
+    }
+}
+
+fn decode_v1_file<'a>(file: Decoder<'a, '_>) -> Result<V1File<'a>> {
+    let pos = file.pos();
+    let file = Dict::decode(file)?;
+
+    let length = file
+        .get_int("length")
+        .ok_or_else(|| Error::missing("length", pos))?;
+    let length = length_from_int(length, pos)?;
+
+    let path = file
+        .get_list("path")
+        .ok_or_else(|| Error::missing("path", pos))?;
+    let path = path
+        .iter()
+        .map(|c| {
+            c.as_str().ok_or(Error::Invalid {
+                reason: "Path component is not a UTF-8 string",
+                pos: c.pos(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let attr = file.get_bytes("attr").unwrap_or_default();
+    Ok(V1File { path, length, attr })
+}
+
+/// The v2 `file tree` dictionary.
+///
+/// Directories are dictionaries keyed by path component and files are
+/// dictionaries with a single empty key `""` holding the file properties.
+#[derive(Debug)]
+pub struct FileTree<'a, 'p> {
+    dict: Dict<'a, 'p>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for FileTree<'a, 'p> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let dict = Dict::decode(decoder)?;
+        Ok(Self { dict })
+    }
+}
+
+impl<'a, 'p> FileTree<'a, 'p> {
+    /// Returns the underlying dictionary.
+    pub fn as_dict(&self) -> &Dict<'a, 'p> {
+        &self.dict
+    }
+
+    /// Returns a depth-first iterator over the files in this tree, in the
+    /// order they appear in the torrent.
+    ///
+    /// The iterator stops after the first error.
+    pub fn iter(&self) -> FileTreeIter<'a, 'p> {
+        FileTreeIter {
+            stack: vec![self.dict.iter()],
+            path: vec![],
+        }
+    }
+}
+
+impl<'a, 'p> IntoIterator for FileTree<'a, 'p> {
+    type Item = Result<FileEntry<'a>>;
+    type IntoIter = FileTreeIter<'a, 'p>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A file in a v2 file tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry<'a> {
+    /// Path components relative to the torrent directory.
+    pub path: Vec<&'a str>,
+
+    /// File size in bytes.
+    pub length: u64,
+
+    /// Root of the file's merkle tree. `None` only for empty files.
+    pub pieces_root: Option<&'a [u8]>,
+}
+
+/// Depth-first iterator over the files of a `FileTree`.
+pub struct FileTreeIter<'a, 'p> {
+    stack: Vec<DictIter<'a, 'p>>,
+    path: Vec<&'a str>,
+}
+
+impl<'a, 'p> Iterator for FileTreeIter<'a, 'p> {
+    type Item = Result<FileEntry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let iter = self.stack.last_mut()?;
+            let (name, node) = match iter.next() {
+                Some(entry) => entry,
+                None => {
+                    self.stack.pop();
+                    self.path.pop();
+                    continue;
+                }
+            };
+
+            let result = self.visit(name, node);
+            match result {
+                Ok(Some(file)) => return Some(Ok(file)),
+                Ok(None) => continue,
+                Err(e) => {
+                    self.stack.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'p> FileTreeIter<'a, 'p> {
+    fn visit(
+        &mut self,
+        name: Decoder<'a, 'p>,
+        node: Decoder<'a, 'p>,
+    ) -> Result<Option<FileEntry<'a>>> {
+        let name = match name.as_str() {
+            Some(name) if !name.is_empty() => name,
+            _ => {
+                return Err(Error::Invalid {
+                    reason: "Invalid file name",
+                    pos: name.pos(),
+                })
+            }
+        };
+
+        let pos = node.pos();
+        let node = match node.into_dict() {
+            Some(node) => node,
+            None => {
+                return Err(Error::Invalid {
+                    reason: "File tree node is not a dictionary",
+                    pos,
+                })
+            }
+        };
+
+        let file = match node.get_dict("") {
+            Some(file) => file,
+            None => {
+                self.path.push(name);
+                self.stack.push(node.iter());
+                return Ok(None);
+            }
+        };
+
+        let pos = file.pos();
+        let length = file
+            .get_int("length")
+            .ok_or_else(|| Error::missing("length", pos))?;
+        let length = length_from_int(length, pos)?;
+
+        let pieces_root = file.get_bytes("pieces root");
+        match pieces_root {
+            Some(root) if root.len() != V2_HASH_LEN => {
+                return Err(Error::Invalid {
+                    reason: "Pieces root must be 32 bytes",
+                    pos,
+                })
+            }
+            None if length > 0 => return Err(Error::missing("pieces root", pos)),
+            _ => {}
+        }
+
+        let mut path = self.path.clone();
+        path.push(name);
+        Ok(Some(FileEntry {
+            path,
+            length,
+            pieces_root,
+        }))
+    }
+}
+
+/// The v2 `piece layers` dictionary, mapping a file's pieces root to the
+/// concatenated hashes of its piece layer.
+#[derive(Debug)]
+pub struct PieceLayers<'a, 'p> {
+    dict: Dict<'a, 'p>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for PieceLayers<'a, 'p> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let dict = Dict::decode(decoder)?;
+        Ok(Self { dict })
+    }
+}
+
+impl<'a, 'p> PieceLayers<'a, 'p> {
+    /// Returns the underlying dictionary.
+    pub fn as_dict(&self) -> &Dict<'a, 'p> {
+        &self.dict
+    }
+
+    /// Returns the raw piece layer for the given pieces root.
+    pub fn get(&self, pieces_root: &[u8]) -> Option<&'a [u8]> {
+        self.dict.get_bytes(pieces_root)
+    }
+
+    /// Returns an iterator over the piece hashes for the given pieces root.
+    pub fn hashes(&self, pieces_root: &[u8]) -> Option<impl Iterator<Item = &'a [u8]>> {
+        let layer = self.get(pieces_root)?;
+        Some(layer.chunks(V2_HASH_LEN))
+    }
+}
+
+fn length_from_int(length: i64, pos: usize) -> Result<u64> {
+    if length < 0 {
+        Err(Error::Invalid {
+            reason: "File length must not be negative",
+            pos,
+        })
+    } else {
+        Ok(length as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+    use crate::Parser;
+
+    const ROOT_A: [u8; 32] = [0xaa; 32];
+    const ROOT_B: [u8; 32] = [0xbb; 32];
+
+    fn file_node(
+        enc: &mut crate::encode::Dict<'_, Vec<u8>>,
+        name: &str,
+        length: i64,
+        root: Option<&[u8; 32]>,
+    ) {
+        let mut node = enc.add_dict(name);
+        let mut file = node.add_dict("");
+        file.add("length", length);
+        if let Some(root) = root {
+            file.add("pieces root", root);
+        }
+    }
+
+    fn hybrid(b_length: i64) -> Vec<u8> {
+        let mut v = vec![];
+        let mut root = v.add_dict();
+        {
+            let mut info = root.add_dict("info");
+            {
+                let mut tree = info.add_dict("file tree");
+                {
+                    let mut dir = tree.add_dict("a");
+                    file_node(&mut dir, "a", 40000, Some(&ROOT_A));
+                }
+                file_node(&mut tree, "b", b_length, Some(&ROOT_B));
+                file_node(&mut tree, "c", 0, None);
+            }
+            {
+                let mut files = info.add_list("files");
+                {
+                    let mut f = files.add_dict();
+                    f.add("length", 40000);
+                    f.add("path", vec!["a", "a"]);
+                }
+                {
+                    let mut f = files.add_dict();
+                    f.add("attr", "p");
+                    f.add("length", 65536 - 40000);
+                    f.add("path", vec![".pad", "25536"]);
+                }
+                {
+                    let mut f = files.add_dict();
+                    f.add("length", 100);
+                    f.add("path", vec!["b"]);
+                }
+                {
+                    let mut f = files.add_dict();
+                    f.add("length", 0);
+                    f.add("path", vec!["c"]);
+                }
+            }
+            info.add("meta version", 2);
+            info.add("name", "test");
+            info.add("piece length", 32768);
+            info.add("pieces", &[0u8; 60][..]);
+        }
+        {
+            let mut layers = root.add_dict("piece layers");
+            layers.add(ROOT_A, &[0x11u8; 64][..]);
+        }
+        root.finish();
+        v
+    }
+
+    #[test]
+    fn file_tree_iter() {
+        let buf = hybrid(100);
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        let info = metainfo.info().unwrap();
+        assert!(info.is_v1());
+        assert!(info.is_v2());
+        assert!(info.is_hybrid());
+
+        let files = info
+            .file_tree()
+            .unwrap()
+            .iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                FileEntry {
+                    path: vec!["a", "a"],
+                    length: 40000,
+                    pieces_root: Some(&ROOT_A[..]),
+                },
+                FileEntry {
+                    path: vec!["b"],
+                    length: 100,
+                    pieces_root: Some(&ROOT_B[..]),
+                },
+                FileEntry {
+                    path: vec!["c"],
+                    length: 0,
+                    pieces_root: None,
+                },
+            ],
+            files
+        );
+    }
+
+    #[test]
+    fn piece_layers() {
+        let buf = hybrid(100);
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        let layers = metainfo.piece_layers().unwrap();
+        assert_eq!(Some(&[0x11; 64][..]), layers.get(&ROOT_A));
+        assert_eq!(2, layers.hashes(&ROOT_A).unwrap().count());
+        assert!(layers.get(&ROOT_B).is_none());
+    }
+
+    #[test]
+    fn validate_hybrid() {
+        let buf = hybrid(100);
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        metainfo.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_hybrid_mismatch() {
+        let buf = hybrid(101);
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&buf).unwrap();
+        let err = metainfo.info().unwrap().validate().unwrap_err();
+        assert_eq!(
+            Error::Invalid {
+                reason: "v1 and v2 file lists differ",
+                pos: 7,
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn validate_missing_piece_layer() {
+        let mut v = vec![];
+        let mut root = v.add_dict();
+        {
+            let mut info = root.add_dict("info");
+            {
+                let mut tree = info.add_dict("file tree");
+                file_node(&mut tree, "a", 40000, Some(&ROOT_A));
+            }
+            info.add("meta version", 2);
+            info.add("name", "a");
+            info.add("piece length", 16384);
+        }
+        root.finish();
+
+        let parser = &mut Parser::new();
+        let metainfo = parser.parse::<Metainfo>(&v).unwrap();
+        metainfo.info().unwrap().validate().unwrap();
+        assert_eq!(
+            Error::Missing {
+                key: "piece layers",
+                pos: 0,
+            },
+            metainfo.validate().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn file_tree_missing_root() {
+        let mut v = vec![];
+        let mut tree = v.add_dict();
+        file_node(&mut tree, "a", 1, None);
+        tree.finish();
+
+        let parser = &mut Parser::new();
+        let tree = parser.parse::<FileTree>(&v).unwrap();
+        let mut iter = tree.iter();
+        assert_eq!(
+            Some(Err(Error::Missing {
+                key: "pieces root",
+                pos: 7,
+            })),
+            iter.next()
+        );
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn v1_single_file() {
+        let mut v = vec![];
+        let mut info = v.add_dict();
+        info.add("length", 10);
+        info.add("name", "a.txt");
+        info.add("piece length", 16384);
+        info.add("pieces", [0u8; 20]);
+        info.finish();
+
+        let parser = &mut Parser::new();
+        let info = parser.parse::<Info>(&v).unwrap();
+        assert!(info.is_v1());
+        assert!(!info.is_v2());
+        info.validate().unwrap();
+
+        let files = info
+            .v1_files()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            vec![V1File {
+                path: vec![],
+                length: 10,
+                attr: &[],
+            }],
+            files
+        );
+    }
+
+    #[test]
+    fn v1_total_length_overflow() {
+        let mut v = vec![];
+        let mut info = v.add_dict();
+        {
+            let mut files = info.add_list("files");
+            for name in &["a", "b", "c"] {
+                let mut f = files.add_dict();
+                f.add("length", i64::MAX);
+                f.add("path", vec![*name]);
+            }
+        }
+        info.add("name", "test");
+        info.add("piece length", 16384);
+        info.add("pieces", [0u8; 20]);
+        info.finish();
+
+        let parser = &mut Parser::new();
+        let info = parser.parse::<Info>(&v).unwrap();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Total length overflows",
+                pos: 0,
+            },
+            info.validate().unwrap_err()
+        );
+    }
+}
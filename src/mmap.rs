@@ -0,0 +1,160 @@
+//! Parsing memory-mapped files.
+//!
+//! `ParsedFile` keeps a file mapping together with the tokens parsed from
+//! it, so large metainfo or fast-resume files can be queried without
+//! reading them into memory first.
+
+use crate::decode::{Decode, Decoder};
+use crate::error::Result;
+use crate::parse::Parser;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// A memory-mapped file and its parsed token tape.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ben::mmap::ParsedFile;
+/// use ben::metainfo::Metainfo;
+///
+/// // Safety: the file is not modified while it is mapped
+/// let file = unsafe { ParsedFile::open("ubuntu.torrent")? };
+/// let metainfo = file.decode::<Metainfo>()?;
+/// println!("{:?}", metainfo.info()?.name());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ParsedFile {
+    mmap: Mmap,
+    parser: Parser,
+}
+
+impl ParsedFile {
+    /// Map and parse the file at `path`.
+    ///
+    /// Parse errors are returned as `io::ErrorKind::InvalidData` wrapping
+    /// an `Error`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, see
+    /// `memmap2::Mmap::map`.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_mmap(Mmap::map(&file)?)
+    }
+
+    /// Parse an existing mapping.
+    pub fn from_mmap(mmap: Mmap) -> io::Result<Self> {
+        Self::with_parser(mmap, Parser::new())
+    }
+
+    /// Parse an existing mapping with given parser, e.g. one configured with
+    /// token or depth limits. The parser is kept to hold the tokens.
+    pub fn with_parser(mmap: Mmap, mut parser: Parser) -> io::Result<Self> {
+        parser
+            .parse::<Decoder>(&mmap)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(ParsedFile { mmap, parser })
+    }
+
+    /// Returns the contents of the file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Returns the parsed token tape.
+    pub fn tokens(&self) -> &[crate::Token] {
+        self.parser.tokens()
+    }
+
+    /// Returns a decoder for the root value of the file.
+    pub fn decoder(&self) -> Decoder<'_, '_> {
+        // A successful parse always produces a root token
+        Decoder::new(&self.mmap, self.parser.tokens()).unwrap()
+    }
+
+    /// Decode the root value of the file.
+    pub fn decode<'p, T: Decode<'p, 'p>>(&'p self) -> Result<T> {
+        T::decode(self.decoder())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Dict;
+    use crate::Error;
+    use std::fs;
+    use std::path::PathBuf;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("ben-{}-{}", name, std::process::id()));
+            fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn open() {
+        let tmp = TempFile::new("mmap-open", b"d1:ai1e1:bl1:x1:yee");
+        let file = unsafe { ParsedFile::open(&tmp.0).unwrap() };
+        assert_eq!(b"d1:ai1e1:bl1:x1:yee", file.as_bytes());
+        assert_eq!(7, file.tokens().len());
+
+        let dict = file.decode::<Dict>().unwrap();
+        assert_eq!(Some(1), dict.get_int("a"));
+        assert_eq!(Some("y"), dict.get_list("b").unwrap().get_str(1));
+    }
+
+    #[test]
+    fn open_invalid() {
+        let tmp = TempFile::new("mmap-invalid", b"d1:a");
+        let err = unsafe { ParsedFile::open(&tmp.0).err().unwrap() };
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        let inner = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_eq!(Error::Eof, *inner);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn open_too_large() {
+        let tmp = TempFile::new("mmap-too-large", b"");
+        // Sparse, so nothing is written or read
+        let file = fs::OpenOptions::new().write(true).open(&tmp.0).unwrap();
+        file.set_len(u64::from(u32::MAX) + 1).unwrap();
+
+        let err = unsafe { ParsedFile::open(&tmp.0).err().unwrap() };
+        let inner = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_eq!(
+            Error::BufferLimit {
+                limit: u32::MAX as usize
+            },
+            *inner
+        );
+    }
+
+    #[test]
+    fn with_parser_limits() {
+        let tmp = TempFile::new("mmap-limits", b"li1ei2ei3ee");
+        let file = File::open(&tmp.0).unwrap();
+        let mmap = unsafe { Mmap::map(&file).unwrap() };
+
+        let mut parser = Parser::new();
+        parser.token_limit(2);
+        let err = ParsedFile::with_parser(mmap, parser).err().unwrap();
+        let inner = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_eq!(Error::TokenLimit { limit: 2 }, *inner);
+    }
+}
@@ -1,24 +1,33 @@
 use crate::decode::{Decode, Decoder};
 use crate::error::{Error, Result};
+use crate::scan;
 use crate::token::{Token, TokenKind};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
 /// Bencode Parser
+///
+/// Inputs longer than `u32::MAX` bytes are rejected with
+/// `Error::BufferLimit`, since token offsets are 32 bits wide.
+#[cfg(feature = "alloc")]
 pub struct Parser {
     tokens: Vec<Token>,
     token_limit: usize,
     depth_limit: usize,
 }
 
+#[cfg(feature = "alloc")]
 impl Default for Parser {
     fn default() -> Self {
         Self {
-            tokens: vec![],
-            token_limit: usize::max_value(),
-            depth_limit: usize::max_value(),
+            tokens: Vec::new(),
+            token_limit: usize::MAX,
+            depth_limit: usize::MAX,
         }
     }
 }
 
+#[cfg(feature = "alloc")]
 impl Parser {
     /// Create a new Bencode parser.
     pub fn new() -> Self {
@@ -43,20 +52,25 @@ impl Parser {
         self.depth_limit = depth_limit
     }
 
+    /// Returns the token tape of the last successful parse.
+    ///
+    /// The contents are unspecified if the last parse failed.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub(crate) fn into_tokens(self) -> Vec<Token> {
+        self.tokens
+    }
+
     /// Parse a bencoded slice and returns the parsed object
     pub fn parse<'a, 'p, T>(&'p mut self, buf: &'a [u8]) -> Result<T>
     where
         T: Decode<'a, 'p>,
     {
         let (dec, len) = self.parse_prefix_impl(buf)?;
-        if len == buf.len() {
-            T::decode(dec)
-        } else {
-            Err(Error::Invalid {
-                reason: "Extra bytes at the end",
-                pos: len,
-            })
-        }
+        check_trailing(buf, len)?;
+        T::decode(dec)
     }
 
     /// Parse one object from the beginning of given slice and return the parsed object and
@@ -73,39 +87,313 @@ impl Parser {
     }
 
     fn parse_prefix_impl<'a, 'p>(&'p mut self, buf: &'a [u8]) -> Result<(Decoder<'a, 'p>, usize)> {
-        if buf.is_empty() {
-            return Err(Error::Eof);
+        self.tokens.clear();
+        let pos = parse_into(buf, &mut self.tokens, self.token_limit, self.depth_limit)?;
+        let d = Decoder::new(buf, &self.tokens).ok_or(Error::Eof)?;
+        Ok((d, pos))
+    }
+}
+
+/// Bencode Parser with a fixed token capacity of `N`.
+///
+/// The tokens are stored inline, so this needs no allocator. Parsing input
+/// with more than `N` tokens fails with `Error::TokenLimit`.
+///
+/// # Examples
+///
+/// ```
+/// use ben::{Decoder, Error, FixedParser};
+///
+/// let parser = &mut FixedParser::<4>::new();
+/// let list = parser.parse::<Decoder>(b"li1ei2ee").unwrap();
+/// assert_eq!(Some(2), list.as_list().unwrap().get_int(1));
+///
+/// let err = parser.parse::<Decoder>(b"li1ei2ei3ei4ee").unwrap_err();
+/// assert_eq!(Error::TokenLimit { limit: 4 }, err);
+/// ```
+pub struct FixedParser<const N: usize> {
+    tokens: [Token; N],
+    len: usize,
+    token_limit: usize,
+    depth_limit: usize,
+}
+
+impl<const N: usize> Default for FixedParser<N> {
+    fn default() -> Self {
+        Self {
+            tokens: [Token::EMPTY; N],
+            len: 0,
+            token_limit: usize::MAX,
+            depth_limit: usize::MAX,
         }
+    }
+}
 
-        self.tokens.clear();
-        let mut state = State {
+impl<const N: usize> FixedParser<N> {
+    /// Create a new Bencode parser.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a limit on number of tokens that can be created during parsing.
+    ///
+    /// The capacity `N` always applies as well.
+    pub fn token_limit(&mut self, token_limit: usize) {
+        self.token_limit = token_limit;
+    }
+
+    /// Set a limit on depth of object nesting that is allowed during parsing.
+    pub fn depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit
+    }
+
+    /// Returns the token tape of the last successful parse.
+    ///
+    /// The contents are unspecified if the last parse failed.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens[..self.len]
+    }
+
+    /// Parse a bencoded slice and returns the parsed object
+    pub fn parse<'a, 'p, T>(&'p mut self, buf: &'a [u8]) -> Result<T>
+    where
+        T: Decode<'a, 'p>,
+    {
+        let (dec, len) = self.parse_prefix_impl(buf)?;
+        check_trailing(buf, len)?;
+        T::decode(dec)
+    }
+
+    /// Parse one object from the beginning of given slice and return the parsed object and
+    /// number of bytes processed.
+    pub fn parse_prefix<'a, 'p, T>(&'p mut self, buf: &'a [u8]) -> Result<(T, usize)>
+    where
+        T: Decode<'a, 'p>,
+    {
+        let (dec, pos) = self.parse_prefix_impl(buf)?;
+        let t = T::decode(dec)?;
+        Ok((t, pos))
+    }
+
+    fn parse_prefix_impl<'a, 'p>(&'p mut self, buf: &'a [u8]) -> Result<(Decoder<'a, 'p>, usize)> {
+        parse_slice(
             buf,
-            pos: 0,
-            tokens: &mut self.tokens,
-            token_limit: self.token_limit,
-            depth_limit: self.depth_limit,
-            current_depth: 0,
-        };
+            &mut self.tokens,
+            &mut self.len,
+            self.token_limit,
+            self.depth_limit,
+        )
+    }
+}
 
-        state.parse_object()?;
-        let pos = state.pos;
-        let d = Decoder::new(buf, &self.tokens).ok_or_else(|| Error::Eof)?;
-        Ok((d, pos))
+/// Bencode Parser writing into a caller-provided token slice.
+///
+/// This needs no allocator and lets the tokens live e.g. on the stack.
+/// Parsing input with more tokens than the slice holds fails with
+/// `Error::TokenLimit`.
+///
+/// # Examples
+///
+/// ```
+/// use ben::{Decoder, SliceParser, Token};
+///
+/// let mut tokens = [Token::default(); 64];
+/// let mut parser = SliceParser::new(&mut tokens);
+/// let dict = parser.parse::<Decoder>(b"d1:ai1ee").unwrap();
+/// assert_eq!(Some(1), dict.as_dict().unwrap().get_int("a"));
+/// ```
+pub struct SliceParser<'t> {
+    tokens: &'t mut [Token],
+    len: usize,
+    token_limit: usize,
+    depth_limit: usize,
+}
+
+impl<'t> SliceParser<'t> {
+    /// Create a new Bencode parser storing tokens in `tokens`.
+    pub fn new(tokens: &'t mut [Token]) -> Self {
+        Self {
+            tokens,
+            len: 0,
+            token_limit: usize::MAX,
+            depth_limit: usize::MAX,
+        }
+    }
+
+    /// Set a limit on number of tokens that can be created during parsing.
+    ///
+    /// The length of the token slice always applies as well.
+    pub fn token_limit(&mut self, token_limit: usize) {
+        self.token_limit = token_limit;
+    }
+
+    /// Set a limit on depth of object nesting that is allowed during parsing.
+    pub fn depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit
+    }
+
+    /// Returns the token tape of the last successful parse.
+    ///
+    /// The contents are unspecified if the last parse failed.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens[..self.len]
+    }
+
+    /// Parse a bencoded slice and returns the parsed object
+    pub fn parse<'a, 'p, T>(&'p mut self, buf: &'a [u8]) -> Result<T>
+    where
+        T: Decode<'a, 'p>,
+    {
+        let (dec, len) = self.parse_prefix_impl(buf)?;
+        check_trailing(buf, len)?;
+        T::decode(dec)
+    }
+
+    /// Parse one object from the beginning of given slice and return the parsed object and
+    /// number of bytes processed.
+    pub fn parse_prefix<'a, 'p, T>(&'p mut self, buf: &'a [u8]) -> Result<(T, usize)>
+    where
+        T: Decode<'a, 'p>,
+    {
+        let (dec, pos) = self.parse_prefix_impl(buf)?;
+        let t = T::decode(dec)?;
+        Ok((t, pos))
+    }
+
+    fn parse_prefix_impl<'a, 'p>(&'p mut self, buf: &'a [u8]) -> Result<(Decoder<'a, 'p>, usize)> {
+        parse_slice(
+            buf,
+            self.tokens,
+            &mut self.len,
+            self.token_limit,
+            self.depth_limit,
+        )
     }
 }
 
-struct State<'a, 't> {
+/// Parse into a token slice. The number of used tokens is stored in `len`
+/// even if parsing fails.
+fn parse_slice<'a, 'p>(
+    buf: &'a [u8],
+    tokens: &'p mut [Token],
+    len: &mut usize,
+    token_limit: usize,
+    depth_limit: usize,
+) -> Result<(Decoder<'a, 'p>, usize)> {
+    let mut slice = SliceTokens { tokens, len: 0 };
+    let result = parse_into(buf, &mut slice, token_limit, depth_limit);
+    *len = slice.len;
+    let pos = result?;
+    let d = Decoder::new(buf, &slice.tokens[..slice.len]).ok_or(Error::Eof)?;
+    Ok((d, pos))
+}
+
+fn check_trailing(buf: &[u8], len: usize) -> Result<()> {
+    if len == buf.len() {
+        Ok(())
+    } else {
+        Err(Error::Invalid {
+            reason: "Extra bytes at the end",
+            pos: len,
+        })
+    }
+}
+
+/// Longest input the parser accepts, as token offsets are stored as `u32`.
+const MAX_BUF_LEN: usize = u32::MAX as usize;
+
+/// Parse one object from the beginning of `buf` into `tokens` and return
+/// the number of bytes processed.
+fn parse_into<T: TokenBuf>(
+    buf: &[u8],
+    tokens: &mut T,
+    token_limit: usize,
+    depth_limit: usize,
+) -> Result<usize> {
+    if buf.is_empty() {
+        return Err(Error::Eof);
+    }
+    if buf.len() > MAX_BUF_LEN {
+        return Err(Error::BufferLimit { limit: MAX_BUF_LEN });
+    }
+
+    let mut state = State {
+        buf,
+        pos: 0,
+        tokens,
+        token_limit,
+        depth_limit,
+        current_depth: 0,
+    };
+
+    state.parse_object()?;
+    Ok(state.pos)
+}
+
+/// Storage the parser appends tokens to.
+trait TokenBuf {
+    fn len(&self) -> usize;
+
+    /// Append a token. Returns `false` if the storage is full.
+    fn push(&mut self, token: Token) -> bool;
+
+    fn get_mut(&mut self, i: usize) -> &mut Token;
+}
+
+#[cfg(feature = "alloc")]
+impl TokenBuf for Vec<Token> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn push(&mut self, token: Token) -> bool {
+        self.push(token);
+        true
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut Token {
+        &mut self[i]
+    }
+}
+
+struct SliceTokens<'t> {
+    tokens: &'t mut [Token],
+    len: usize,
+}
+
+impl TokenBuf for SliceTokens<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, token: Token) -> bool {
+        match self.tokens.get_mut(self.len) {
+            Some(slot) => {
+                *slot = token;
+                self.len += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut Token {
+        &mut self.tokens[..self.len][i]
+    }
+}
+
+struct State<'a, 't, T> {
     buf: &'a [u8],
     pos: usize,
-    tokens: &'t mut Vec<Token>,
+    tokens: &'t mut T,
     token_limit: usize,
     depth_limit: usize,
     current_depth: usize,
 }
 
-impl<'a, 't> State<'a, 't> {
+impl<T: TokenBuf> State<'_, '_, T> {
     fn peek_char(&self) -> Result<u8> {
-        self.buf.get(self.pos).copied().ok_or_else(|| Error::Eof)
+        self.buf.get(self.pos).copied().ok_or(Error::Eof)
     }
 
     fn next_char(&mut self) -> Result<u8> {
@@ -152,7 +440,7 @@ impl<'a, 't> State<'a, 't> {
         self.next_char()?;
 
         let next = self.tokens.len() - token_pos;
-        let token = &mut self.tokens[token_pos];
+        let token = self.tokens.get_mut(token_pos);
         token.end = self.pos as u32;
         token.children = children;
         token.next = next as u32;
@@ -176,7 +464,7 @@ impl<'a, 't> State<'a, 't> {
         self.next_char()?;
 
         let next = self.tokens.len() - token_pos;
-        let token = &mut self.tokens[token_pos];
+        let token = self.tokens.get_mut(token_pos);
         token.end = self.pos as u32;
         token.children = children;
         token.next = next as u32;
@@ -190,62 +478,79 @@ impl<'a, 't> State<'a, 't> {
 
         let token_pos = self.create_token(TokenKind::Int)?;
 
-        // Can be negative
+        // Can be negative, which allows one more than `i64::MAX`
+        let mut max = i64::MAX as u64;
         if self.peek_char()? == b'-' {
             self.pos += 1;
+            max += 1;
         }
 
         if self.peek_char()? == b'e' {
             return Err(Error::Unexpected { pos: self.pos });
         }
 
-        let mut val: i64 = 0;
-
-        loop {
-            match self.next_char()? {
-                c @ b'0'..=b'9' => {
-                    let digit = i64::from(c - b'0');
-                    match val.checked_mul(10).and_then(|n| n.checked_add(digit)) {
-                        Some(n) => val = n,
-                        None => return Err(Error::Overflow { pos: self.pos }),
-                    }
-                }
-                b'e' => {
-                    self.tokens[token_pos].end = (self.pos - 1) as u32;
-                    return Ok(());
-                }
-                _ => return Err(Error::Unexpected { pos: self.pos - 1 }),
+        match self.parse_digits(max)? {
+            (_, b'e') => {
+                self.tokens.get_mut(token_pos).end = (self.pos - 1) as u32;
+                Ok(())
             }
+            _ => Err(Error::Unexpected { pos: self.pos - 1 }),
         }
     }
 
     fn parse_string(&mut self) -> Result<()> {
-        let mut len: usize = 0;
-
-        loop {
-            match self.next_char()? {
-                c @ b'0'..=b'9' => {
-                    let digit = (c - b'0') as usize;
-                    match len.checked_mul(10).and_then(|n| n.checked_add(digit)) {
-                        Some(n) => len = n,
-                        None => return Err(Error::Overflow { pos: self.pos }),
-                    }
-                }
-                b':' => break,
-                _ => return Err(Error::Unexpected { pos: self.pos }),
-            }
+        // Dict keys come here directly, so the length may not be checked yet
+        if !self.peek_char()?.is_ascii_digit() {
+            return Err(Error::Unexpected { pos: self.pos });
         }
 
-        if self.pos + len <= self.buf.len() {
+        let len = match self.parse_digits(usize::MAX as u64)? {
+            (len, b':') => len as usize,
+            _ => return Err(Error::Unexpected { pos: self.pos }),
+        };
+
+        if len <= self.buf.len() - self.pos {
             let token_pos = self.create_token(TokenKind::ByteStr)?;
             self.pos += len;
-            self.tokens[token_pos].end = self.pos as u32;
+            self.tokens.get_mut(token_pos).end = self.pos as u32;
             Ok(())
         } else {
             Err(Error::Eof)
         }
     }
 
+    /// Parse a run of digits as a number not exceeding `max` and consume
+    /// the byte after it, which is returned as well.
+    #[inline(always)]
+    fn parse_digits(&mut self, max: u64) -> Result<(u64, u8)> {
+        let start = self.pos;
+        let mut n = 0;
+        loop {
+            let c = self.next_char()?;
+            if !c.is_ascii_digit() {
+                return Ok((n, c));
+            }
+            if self.pos - start > scan::SHORT_RUN {
+                break;
+            }
+            n = n * 10 + u64::from(c - b'0');
+        }
+        self.parse_long_digits(start, max)
+    }
+
+    /// Parse a run of more than `scan::SHORT_RUN` digits. Kept out of line
+    /// so that the short path stays small.
+    #[inline(never)]
+    fn parse_long_digits(&mut self, start: usize, max: u64) -> Result<(u64, u8)> {
+        match scan::parse_number(&self.buf[start..], max) {
+            Ok((n, run)) => {
+                self.pos = start + run;
+                Ok((n, self.next_char()?))
+            }
+            Err(i) => Err(Error::Overflow { pos: start + i + 1 }),
+        }
+    }
+
     fn create_token(&mut self, kind: TokenKind) -> Result<usize> {
         if self.tokens.len() == self.token_limit {
             return Err(Error::TokenLimit {
@@ -253,7 +558,11 @@ impl<'a, 't> State<'a, 't> {
             });
         }
         let token = Token::new(kind, self.pos as u32, self.pos as u32);
-        self.tokens.push(token);
+        if !self.tokens.push(token) {
+            return Err(Error::TokenLimit {
+                limit: self.tokens.len(),
+            });
+        }
         Ok(self.tokens.len() - 1)
     }
 }
@@ -261,6 +570,7 @@ impl<'a, 't> State<'a, 't> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::decode::Dict;
 
     #[test]
     fn parse_int() {
@@ -300,6 +610,42 @@ mod tests {
         assert_eq!(Error::Eof, err);
     }
 
+    #[test]
+    fn parse_long_digit_runs() {
+        let parser = &mut Parser::new();
+
+        let s = b"i000000000000000000000000000000042e";
+        assert_eq!(Some(42), parser.parse::<Decoder>(s).unwrap().as_int());
+
+        let s = b"i-9223372036854775807e";
+        let n = parser.parse::<Decoder>(s).unwrap().as_int();
+        assert_eq!(Some(-9223372036854775807), n);
+
+        let s = b"i9223372036854775808e";
+        let err = parser.parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Overflow { pos: 20 }, err);
+
+        let s = b"99999999999999999999999999:";
+        let err = parser.parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Overflow { pos: 20 }, err);
+
+        let s = b"18446744073709551615:";
+        let err = parser.parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Eof, err);
+
+        let s = b"i12345678901234567x";
+        let err = parser.parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Unexpected { pos: 18 }, err);
+
+        let s = b"12345678901234567x";
+        let err = parser.parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Unexpected { pos: 18 }, err);
+
+        let s = b"i12345678901234567";
+        let err = parser.parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Eof, err);
+    }
+
     #[test]
     fn empty_dict() {
         let s = b"de";
@@ -329,6 +675,13 @@ mod tests {
         assert_eq!(Error::Unexpected { pos: 10 }, err);
     }
 
+    #[test]
+    fn key_without_length() {
+        let s = b"d:lee";
+        let err = Parser::new().parse::<Decoder>(s).unwrap_err();
+        assert_eq!(Error::Unexpected { pos: 1 }, err);
+    }
+
     #[test]
     fn dict_string_values() {
         let s = b"d1:a2:ab3:abc4:abcde";
@@ -534,4 +887,68 @@ mod tests {
             &parser.tokens[..]
         );
     }
+
+    #[test]
+    fn fixed_parser() {
+        let s = b"d1:ali1ei2ee1:b0:e";
+        let mut parser = FixedParser::<7>::new();
+        let dict = parser.parse::<Dict>(s).unwrap();
+        assert_eq!(Some(&b""[..]), dict.get_bytes("b"));
+        assert_eq!(Some(2), dict.get_list("a").unwrap().get_int(1));
+
+        let mut vec_parser = Parser::new();
+        vec_parser.parse::<Decoder>(s).unwrap();
+        assert_eq!(&vec_parser.tokens[..], &parser.tokens[..parser.len]);
+    }
+
+    #[test]
+    fn fixed_parser_token_limit() {
+        let mut parser = FixedParser::<7>::new();
+        let err = parser
+            .parse::<Decoder>(b"d1:ali1ei2ee1:b0:1:ci3ee")
+            .unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 7 }, err);
+
+        // The parser is still usable after hitting the limit
+        let n = parser.parse::<i64>(b"i7e").unwrap();
+        assert_eq!(7, n);
+    }
+
+    #[test]
+    fn fixed_parser_depth_limit() {
+        let mut parser = FixedParser::<8>::new();
+        parser.depth_limit(2);
+        let err = parser.parse::<Decoder>(b"llleee").unwrap_err();
+        assert_eq!(Error::DepthLimit { limit: 2 }, err);
+    }
+
+    #[test]
+    fn slice_parser() {
+        let s = b"d1:ali1ei2ee1:b0:e";
+        let mut tokens = [Token::default(); 7];
+        let mut parser = SliceParser::new(&mut tokens);
+        let dict = parser.parse::<Dict>(s).unwrap();
+        assert_eq!(Some(2), dict.get_list("a").unwrap().get_int(1));
+
+        let err = parser
+            .parse::<Decoder>(b"li1ei2ei3ei4ei5ei6ei7ee")
+            .unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 7 }, err);
+
+        parser.token_limit(2);
+        let err = parser.parse::<Decoder>(b"li1ei2ee").unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 2 }, err);
+
+        parser.depth_limit(1);
+        let err = parser.parse::<Decoder>(b"lle").unwrap_err();
+        assert_eq!(Error::DepthLimit { limit: 1 }, err);
+    }
+
+    #[test]
+    fn slice_parser_empty() {
+        let mut parser = SliceParser::new(&mut []);
+        let err = parser.parse::<Decoder>(b"i1e").unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 0 }, err);
+        assert_eq!(Error::Eof, parser.parse::<Decoder>(b"").unwrap_err());
+    }
 }
@@ -0,0 +1,278 @@
+//! Sharing warm parsers between threads.
+//!
+//! A `Parser` keeps its token vector between parses, so reusing one avoids
+//! reallocating it for every message. `ParserPool` hands out parsers to any
+//! number of threads and takes them back once they are dropped.
+//!
+//! With the `parallel` feature `ParserPool::parse_batch` parses a batch of
+//! buffers in parallel on the rayon thread pool.
+
+use crate::decode::Decoder;
+use crate::error::Result;
+use crate::parse::Parser;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+/// A thread-safe pool of `Parser`s.
+///
+/// # Examples
+///
+/// ```
+/// use ben::pool::ParserPool;
+/// use std::thread;
+///
+/// let pool = ParserPool::new();
+/// thread::scope(|s| {
+///     for i in 0..4 {
+///         let pool = &pool;
+///         s.spawn(move || {
+///             let buf = format!("li{}ee", i);
+///             let mut parser = pool.get();
+///             let list = parser.parse::<ben::Decoder>(buf.as_bytes()).unwrap();
+///             assert_eq!(Some(i), list.as_list().unwrap().get_int(0));
+///         });
+///     }
+/// });
+/// ```
+pub struct ParserPool {
+    parsers: Mutex<Vec<Parser>>,
+    capacity: usize,
+    token_limit: usize,
+    depth_limit: usize,
+}
+
+impl Default for ParserPool {
+    fn default() -> Self {
+        Self {
+            parsers: Mutex::new(Vec::new()),
+            capacity: 0,
+            token_limit: usize::MAX,
+            depth_limit: usize::MAX,
+        }
+    }
+}
+
+impl ParserPool {
+    /// Create a new empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new empty pool whose parsers start with given token capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    /// Set the token limit of the pooled parsers.
+    pub fn token_limit(&mut self, token_limit: usize) {
+        self.token_limit = token_limit;
+    }
+
+    /// Set the depth limit of the pooled parsers.
+    pub fn depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit;
+    }
+
+    /// Returns the number of idle parsers in the pool.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Take a parser from the pool, creating one if none is idle.
+    ///
+    /// The pool's limits are applied every time, so limits changed by a
+    /// previous borrower don't carry over. The parser is returned to the
+    /// pool when the guard is dropped.
+    pub fn get(&self) -> PooledParser<'_> {
+        let mut parser = self
+            .lock()
+            .pop()
+            .unwrap_or_else(|| Parser::with_capacity(self.capacity));
+        parser.token_limit(self.token_limit);
+        parser.depth_limit(self.depth_limit);
+        PooledParser {
+            pool: self,
+            parser: Some(parser),
+        }
+    }
+
+    /// Parse given buffer with a pooled parser and pass the root `Decoder`
+    /// to `f`.
+    pub fn parse<F, R>(&self, buf: &[u8], f: F) -> Result<R>
+    where
+        F: FnOnce(Decoder<'_, '_>) -> Result<R>,
+    {
+        let mut parser = self.get();
+        let decoder = parser.parse::<Decoder>(buf)?;
+        f(decoder)
+    }
+
+    /// Parse every buffer in parallel and pass each root `Decoder` to `f`.
+    ///
+    /// Returns one result per buffer, in the same order as `bufs`. A failed
+    /// message doesn't affect the others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::pool::ParserPool;
+    ///
+    /// let pool = ParserPool::new();
+    /// let bufs = vec![&b"i1e"[..], b"i2", b"i3e"];
+    /// let ints = pool.parse_batch(&bufs, |d| Ok(d.as_int()));
+    /// assert_eq!(Ok(Some(1)), ints[0]);
+    /// assert_eq!(Err(ben::Error::Eof), ints[1]);
+    /// assert_eq!(Ok(Some(3)), ints[2]);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn parse_batch<B, F, R>(&self, bufs: &[B], f: F) -> Vec<Result<R>>
+    where
+        B: AsRef<[u8]> + Sync,
+        F: Fn(Decoder<'_, '_>) -> Result<R> + Sync,
+        R: Send,
+    {
+        use rayon::prelude::*;
+
+        bufs.par_iter()
+            .map_init(
+                || self.get(),
+                |parser, buf| {
+                    let decoder = parser.parse::<Decoder>(buf.as_ref())?;
+                    f(decoder)
+                },
+            )
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Parser>> {
+        // Parsers hold no state across parses, so a poisoned pool is fine
+        self.parsers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A `Parser` borrowed from a `ParserPool`.
+pub struct PooledParser<'a> {
+    pool: &'a ParserPool,
+    parser: Option<Parser>,
+}
+
+impl Deref for PooledParser<'_> {
+    type Target = Parser;
+
+    fn deref(&self) -> &Parser {
+        self.parser.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledParser<'_> {
+    fn deref_mut(&mut self) -> &mut Parser {
+        self.parser.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledParser<'_> {
+    fn drop(&mut self) {
+        if let Some(parser) = self.parser.take() {
+            self.pool.lock().push(parser);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn reuse() {
+        let pool = ParserPool::with_capacity(16);
+        assert_eq!(0, pool.idle());
+
+        {
+            let mut parser = pool.get();
+            parser.parse::<Decoder>(b"li1ei2ee").unwrap();
+            assert_eq!(0, pool.idle());
+        }
+        assert_eq!(1, pool.idle());
+
+        let a = pool.get();
+        let b = pool.get();
+        assert_eq!(0, pool.idle());
+        drop(a);
+        drop(b);
+        assert_eq!(2, pool.idle());
+    }
+
+    #[test]
+    fn limits() {
+        let mut pool = ParserPool::new();
+        pool.token_limit(2);
+        let err = pool.parse(b"li1ei2ee", |_| Ok(())).unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 2 }, err);
+
+        pool.depth_limit(1);
+        let err = pool.parse(b"lli1eee", |_| Ok(())).unwrap_err();
+        assert_eq!(Error::DepthLimit { limit: 1 }, err);
+    }
+
+    #[test]
+    fn limits_reapplied() {
+        let mut pool = ParserPool::new();
+        pool.token_limit(3);
+        pool.depth_limit(2);
+        {
+            let mut parser = pool.get();
+            parser.token_limit(usize::MAX);
+            parser.depth_limit(usize::MAX);
+            parser.parse::<Decoder>(b"lli1ei2eee").unwrap();
+        }
+        assert_eq!(1, pool.idle());
+
+        let err = pool.parse(b"li1ei2ei3ee", |_| Ok(())).unwrap_err();
+        assert_eq!(Error::TokenLimit { limit: 3 }, err);
+        let err = pool.parse(b"llleee", |_| Ok(())).unwrap_err();
+        assert_eq!(Error::DepthLimit { limit: 2 }, err);
+    }
+
+    #[test]
+    fn parse() {
+        let pool = ParserPool::new();
+        let n = pool
+            .parse(b"d1:ai7ee", |d| Ok(d.into_dict().unwrap().get_int("a")))
+            .unwrap();
+        assert_eq!(Some(7), n);
+        assert_eq!(1, pool.idle());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parse_batch() {
+        let pool = ParserPool::new();
+        let bufs: Vec<Vec<u8>> = (0..1000)
+            .map(|i| {
+                if i % 100 == 99 {
+                    b"d1:a".to_vec()
+                } else {
+                    format!("d1:ai{}ee", i).into_bytes()
+                }
+            })
+            .collect();
+
+        let results = pool.parse_batch(&bufs, |d| {
+            let dict = d.into_dict().unwrap();
+            Ok(dict.get_int("a").unwrap())
+        });
+        assert_eq!(1000, results.len());
+        for (i, res) in results.into_iter().enumerate() {
+            if i % 100 == 99 {
+                assert_eq!(Err(Error::Eof), res);
+            } else {
+                assert_eq!(Ok(i as i64), res);
+            }
+        }
+        assert!(pool.idle() >= 1);
+    }
+}
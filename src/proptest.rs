@@ -0,0 +1,405 @@
+//! Property-testing strategies for bencode documents.
+//!
+//! `value` and `document` generate canonical bencode, shaped by a `Config`,
+//! and `check_round_trip` verifies that a type encodes to canonical bencode
+//! which decodes back to an equal value.
+//!
+//! # Examples
+//!
+//! ```
+//! use ben::proptest::{check_round_trip, document, Bytes, Config};
+//! use ben::Parser;
+//! use proptest::prelude::*;
+//!
+//! let mut config = Config::new();
+//! config.depth(2).keys(["id", "q", "t", "y"]).bytes(Bytes::Exact(20));
+//!
+//! proptest!(|(doc in document(&config), n: i64)| {
+//!     Parser::new().parse::<ben::Decoder>(&doc).unwrap();
+//!     check_round_trip(&n)?;
+//! });
+//! ```
+
+use crate::decode::Decode;
+use crate::encode::Encode;
+use crate::value::Value;
+use crate::Parser;
+use core::fmt::Debug;
+use core::ops::{Range, RangeInclusive};
+use proptest::arbitrary::Arbitrary;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::strategy::{BoxedStrategy, Union};
+use proptest::test_runner::TestCaseError;
+use std::collections::BTreeMap;
+
+/// Distribution of generated byte strings.
+///
+/// Ranges must not be empty and `Weighted` needs at least one choice, all
+/// with non-zero weights. `strategy` and the `Config` setters panic
+/// otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bytes {
+    /// Arbitrary bytes with a length in the range.
+    Any(Range<usize>),
+    /// Arbitrary bytes of exactly this length, e.g. 20 for info hashes and
+    /// node IDs.
+    Exact(usize),
+    /// ASCII alphanumerics with a length in the range.
+    Ascii(Range<usize>),
+    /// One of the given distributions, picked with the given weight.
+    Weighted(Vec<(u32, Bytes)>),
+}
+
+impl Bytes {
+    /// Returns a strategy generating byte strings from this distribution.
+    pub fn strategy(&self) -> BoxedStrategy<Vec<u8>> {
+        self.validate();
+        match self {
+            Bytes::Any(len) => vec(any::<u8>(), len.clone()).boxed(),
+            Bytes::Exact(len) => vec(any::<u8>(), *len).boxed(),
+            Bytes::Ascii(len) => {
+                let alnum = prop_oneof![b'0'..=b'9', b'A'..=b'Z', b'a'..=b'z'];
+                vec(alnum, len.clone()).boxed()
+            }
+            Bytes::Weighted(choices) => {
+                let choices = choices.iter().map(|(w, b)| (*w, b.strategy())).collect();
+                Union::new_weighted(choices).boxed()
+            }
+        }
+    }
+
+    fn validate(&self) {
+        match self {
+            Bytes::Any(len) => assert!(!len.is_empty(), "Bytes::Any range {:?} is empty", len),
+            Bytes::Ascii(len) => {
+                assert!(!len.is_empty(), "Bytes::Ascii range {:?} is empty", len)
+            }
+            Bytes::Exact(_) => {}
+            Bytes::Weighted(choices) => {
+                assert!(!choices.is_empty(), "Bytes::Weighted has no choices");
+                for (w, b) in choices {
+                    assert!(*w > 0, "Bytes::Weighted has a choice with weight 0");
+                    b.validate();
+                }
+            }
+        }
+    }
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Bytes::Any(0..64)
+    }
+}
+
+/// Shape of generated documents.
+#[derive(Debug, Clone)]
+pub struct Config {
+    depth: u32,
+    size: u32,
+    len: usize,
+    keys: Option<Vec<Vec<u8>>>,
+    key_bytes: Bytes,
+    bytes: Bytes,
+    ints: RangeInclusive<i64>,
+}
+
+impl Config {
+    /// Create a new config. Documents nest up to 4 levels with up to 8
+    /// items per list or dict.
+    pub fn new() -> Self {
+        Self {
+            depth: 4,
+            size: 64,
+            len: 8,
+            keys: None,
+            key_bytes: Bytes::Ascii(1..16),
+            bytes: Bytes::default(),
+            ints: i64::MIN..=i64::MAX,
+        }
+    }
+
+    /// Set the maximum nesting depth of lists and dicts. A depth of 0 only
+    /// generates integers and byte strings.
+    pub fn depth(&mut self, depth: u32) -> &mut Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the number of values a document is aimed at.
+    pub fn size(&mut self, size: u32) -> &mut Self {
+        self.size = size;
+        self
+    }
+
+    /// Set the maximum number of items in each list or dict.
+    pub fn len(&mut self, len: usize) -> &mut Self {
+        self.len = len;
+        self
+    }
+
+    /// Draw dict keys from the given set rather than from the key
+    /// distribution. Panics if the set is empty.
+    pub fn keys<I, K>(&mut self, keys: I) -> &mut Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<[u8]>,
+    {
+        let keys: Vec<_> = keys.into_iter().map(|k| k.as_ref().to_vec()).collect();
+        assert!(!keys.is_empty(), "Config::keys needs at least one key");
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Set the distribution of dict keys. Ignored if a key set is given.
+    /// Panics if `bytes` is invalid.
+    pub fn key_bytes(&mut self, bytes: Bytes) -> &mut Self {
+        bytes.validate();
+        self.key_bytes = bytes;
+        self
+    }
+
+    /// Set the distribution of byte string values. Panics if `bytes` is
+    /// invalid.
+    pub fn bytes(&mut self, bytes: Bytes) -> &mut Self {
+        bytes.validate();
+        self.bytes = bytes;
+        self
+    }
+
+    /// Set the range of integer values. Panics if the range is empty.
+    pub fn ints(&mut self, ints: RangeInclusive<i64>) -> &mut Self {
+        assert!(!ints.is_empty(), "Config::ints range {:?} is empty", ints);
+        self.ints = ints;
+        self
+    }
+
+    fn key_strategy(&self) -> BoxedStrategy<Vec<u8>> {
+        match &self.keys {
+            Some(keys) => proptest::sample::select(keys.clone()).boxed(),
+            None => self.key_bytes.strategy(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a strategy generating values shaped by `config`.
+pub fn value(config: &Config) -> BoxedStrategy<Value> {
+    let leaf = prop_oneof![
+        config.ints.clone().prop_map(Value::Int),
+        config.bytes.strategy().prop_map(Value::Bytes),
+    ];
+    let len = config.len;
+    let keys = config.key_strategy();
+    leaf.prop_recursive(config.depth, config.size, len as u32, move |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..=len).prop_map(Value::List),
+            // Duplicate keys collapse, so small key sets never cause rejects
+            vec((keys.clone(), inner), 0..=len)
+                .prop_map(|entries| Value::Dict(entries.into_iter().collect::<BTreeMap<_, _>>())),
+        ]
+    })
+    .boxed()
+}
+
+/// Returns a strategy generating canonical bencode documents shaped by
+/// `config`.
+pub fn document(config: &Config) -> BoxedStrategy<Vec<u8>> {
+    value(config).prop_map(|v| v.encode_to_vec()).boxed()
+}
+
+impl Arbitrary for Value {
+    type Parameters = Config;
+    type Strategy = BoxedStrategy<Value>;
+
+    fn arbitrary_with(config: Config) -> Self::Strategy {
+        value(&config)
+    }
+}
+
+/// Check that `value` encodes to canonical bencode of its `encoded_len`
+/// which decodes back to an equal value.
+///
+/// Meant for use with `?` inside `proptest!`.
+pub fn check_round_trip<T>(value: &T) -> Result<(), TestCaseError>
+where
+    T: Encode + for<'a, 'p> Decode<'a, 'p> + PartialEq + Debug,
+{
+    let buf = value.encode_to_vec();
+    prop_assert_eq!(buf.len(), value.encoded_len(), "encoded_len mismatch");
+
+    let mut parser = Parser::new();
+    let canonical = parser
+        .parse::<Value>(&buf)
+        .map_err(|e| TestCaseError::fail(format!("invalid bencode: {}", e)))?
+        .encode_to_vec();
+    prop_assert_eq!(&canonical, &buf, "not canonical bencode");
+
+    let decoded = parser
+        .parse::<T>(&buf)
+        .map_err(|e| TestCaseError::fail(format!("decode failed: {}", e)))?;
+    prop_assert_eq!(value, &decoded);
+    Ok(())
+}
+
+/// Like `check_round_trip` but panics on failure.
+pub fn assert_round_trip<T>(value: &T)
+where
+    T: Encode + for<'a, 'p> Decode<'a, 'p> + PartialEq + Debug,
+{
+    if let Err(e) = check_round_trip(value) {
+        panic!("{}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Decoder;
+
+    fn depth(value: &Value) -> u32 {
+        match value {
+            Value::List(list) => 1 + list.iter().map(depth).max().unwrap_or(0),
+            Value::Dict(dict) => 1 + dict.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn all_bytes<'v>(value: &'v Value, out: &mut Vec<&'v [u8]>) {
+        match value {
+            Value::Bytes(b) => out.push(b),
+            Value::List(list) => list.iter().for_each(|v| all_bytes(v, out)),
+            Value::Dict(dict) => dict.values().for_each(|v| all_bytes(v, out)),
+            Value::Int(_) => {}
+        }
+    }
+
+    fn all_keys<'v>(value: &'v Value, out: &mut Vec<&'v [u8]>) {
+        match value {
+            Value::List(list) => list.iter().for_each(|v| all_keys(v, out)),
+            Value::Dict(dict) => dict.iter().for_each(|(k, v)| {
+                out.push(k);
+                all_keys(v, out);
+            }),
+            _ => {}
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn value_round_trip(value: Value) {
+            check_round_trip(&value)?;
+        }
+
+        #[test]
+        fn document_is_canonical(doc in document(&Config::new())) {
+            let mut parser = Parser::new();
+            let decoder = parser.parse::<Decoder>(&doc).unwrap();
+            prop_assert_eq!(&doc, &decoder.encode_to_vec());
+        }
+
+        #[test]
+        fn config_shapes_values(
+            value in any_with::<Value>(
+                Config::new()
+                    .depth(2)
+                    .keys(["a", "b"])
+                    .bytes(Bytes::Exact(20))
+                    .ints(-5..=5)
+                    .clone()
+            )
+        ) {
+            prop_assert!(depth(&value) <= 2);
+
+            let mut bytes = Vec::new();
+            all_bytes(&value, &mut bytes);
+            prop_assert!(bytes.iter().all(|b| b.len() == 20));
+
+            let mut keys = Vec::new();
+            all_keys(&value, &mut keys);
+            prop_assert!(keys.iter().all(|&k| k == b"a" || k == b"b"));
+
+            if let Value::Int(n) = value {
+                prop_assert!((-5..=5).contains(&n));
+            }
+        }
+
+        #[test]
+        fn weighted_bytes(
+            b in Bytes::Weighted(vec![(1, Bytes::Exact(6)), (1, Bytes::Ascii(1..3))]).strategy()
+        ) {
+            prop_assert!(b.len() == 6 || (1..3).contains(&b.len()));
+        }
+
+        #[test]
+        fn checked_list_accepts_documents(doc in document(&Config::new())) {
+            struct Raw<'a>(&'a [u8]);
+
+            impl Encode for Raw<'_> {
+                fn encode<E: crate::Encoder>(&self, enc: &mut E) {
+                    enc.write(self.0);
+                }
+            }
+
+            let mut buf = vec![];
+            let mut list = crate::Encoder::add_checked_list(&mut buf);
+            prop_assert_eq!(Ok(()), list.add(Raw(&doc)));
+        }
+
+        #[test]
+        fn int_round_trip(n: i64) {
+            check_round_trip(&n)?;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Bytes::Weighted has no choices")]
+    fn weighted_bytes_empty() {
+        let _ = Bytes::Weighted(vec![]).strategy();
+    }
+
+    #[test]
+    #[should_panic(expected = "Bytes::Weighted has a choice with weight 0")]
+    fn weighted_bytes_zero_weight() {
+        Config::new().bytes(Bytes::Weighted(vec![(0, Bytes::Exact(1))]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bytes::Ascii range 3..3 is empty")]
+    fn bytes_empty_range() {
+        Config::new().key_bytes(Bytes::Weighted(vec![(1, Bytes::Ascii(3..3))]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Config::keys needs at least one key")]
+    fn keys_empty() {
+        Config::new().keys(Vec::<&str>::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "not canonical")]
+    fn non_canonical_encoding() {
+        #[derive(Debug, PartialEq)]
+        struct Unsorted;
+
+        impl Encode for Unsorted {
+            fn encode<E: crate::Encoder>(&self, enc: &mut E) {
+                enc.write(b"d1:bi1e1:ai2ee");
+            }
+        }
+
+        impl<'a, 'p> Decode<'a, 'p> for Unsorted {
+            fn decode(_: Decoder<'a, 'p>) -> crate::Result<Self> {
+                Ok(Unsorted)
+            }
+        }
+
+        assert_round_trip(&Unsorted);
+    }
+}
@@ -0,0 +1,289 @@
+//! Digit scanning used by the parser for integers and string lengths.
+//!
+//! Runs of up to 4 digits, which covers almost all string lengths, are
+//! parsed by a plain loop in the parser. Longer runs are found with a
+//! single 16 byte vector compare, as a valid length or integer has at most
+//! 20 digits, and parsed 8 digits at a time. SSE2 is used on x86 and NEON on aarch64;
+//! other targets use a scalar loop instead. Wider vectors such as AVX2
+//! would only ever look at bytes past the run.
+
+/// Parse the run of ASCII digits at the start of `buf` as a number which
+/// must not exceed `max`.
+///
+/// Returns the number and the length of the run, or on overflow the index
+/// of the digit at which the value first exceeded `max`.
+#[inline]
+pub(crate) fn parse_number(buf: &[u8], max: u64) -> core::result::Result<(u64, usize), usize> {
+    if let Some(chunk) = buf.first_chunk::<16>() {
+        let run = (!simd::digit_mask(chunk)).trailing_zeros() as usize;
+        if run <= 8 {
+            // Left pad the digits with '0' so that they can be parsed at once
+            let word = u64::from_le_bytes(*buf.first_chunk::<8>().unwrap());
+            let word = match run {
+                0 => ZEROS,
+                8 => word,
+                _ => (word << (8 * (8 - run))) | (ZEROS >> (8 * run)),
+            };
+            let n = parse_8(word);
+            if n <= max {
+                return Ok((n, run));
+            }
+        }
+    }
+    let run = digit_run(buf);
+    parse_digits(&buf[..run], max).map(|n| (n, run))
+}
+
+/// Digit runs up to this length should be parsed by a plain loop.
+///
+/// Such runs cover almost all string lengths. A loop with well predicted
+/// branches is faster for them, as the vector path adds latency to every
+/// token.
+pub(crate) const SHORT_RUN: usize = 4;
+
+/// Returns the number of leading ASCII digits in `buf`.
+#[inline]
+fn digit_run(buf: &[u8]) -> usize {
+    let mut i = 0;
+    while let Some(chunk) = buf[i..].first_chunk::<16>() {
+        let mask = simd::digit_mask(chunk);
+        if mask != 0xFFFF {
+            return i + (!mask).trailing_zeros() as usize;
+        }
+        i += 16;
+    }
+    i + digit_run_scalar(&buf[i..])
+}
+
+#[inline]
+fn digit_run_scalar(buf: &[u8]) -> usize {
+    buf.iter().take_while(|c| c.is_ascii_digit()).count()
+}
+
+/// Parse a run of ASCII digits as a number which must not exceed `max`.
+///
+/// On overflow returns the index of the digit at which the value first
+/// exceeded `max`.
+#[inline]
+fn parse_digits(digits: &[u8], max: u64) -> core::result::Result<u64, usize> {
+    debug_assert!(digits.iter().all(u8::is_ascii_digit));
+
+    // 18 digits always fit in a u64, so the fast path needs no checks
+    if digits.len() <= 18 {
+        let mut n = 0;
+        let mut chunks = digits.chunks_exact(8);
+        for chunk in &mut chunks {
+            n = n * 100_000_000 + parse_8(u64::from_le_bytes(*chunk.first_chunk().unwrap()));
+        }
+        for &c in chunks.remainder() {
+            n = n * 10 + u64::from(c - b'0');
+        }
+        if n <= max {
+            return Ok(n);
+        }
+    }
+
+    let mut n: u64 = 0;
+    for (i, &c) in digits.iter().enumerate() {
+        match n
+            .checked_mul(10)
+            .and_then(|n| n.checked_add(u64::from(c - b'0')))
+            .filter(|&n| n <= max)
+        {
+            Some(v) => n = v,
+            None => return Err(i),
+        }
+    }
+    Ok(n)
+}
+
+const ZEROS: u64 = 0x3030_3030_3030_3030;
+
+/// Parse 8 ASCII digits loaded as a little endian word at once.
+#[inline]
+fn parse_8(word: u64) -> u64 {
+    // First digit in the lowest byte
+    let v = word - ZEROS;
+    // Combine adjacent digits into 2 digit numbers in every other byte
+    let v = v * 10 + (v >> 8);
+    // Combine those into two 4 digit numbers and finally one 8 digit number
+    let lo = (v & 0x0000_00FF_0000_00FF).wrapping_mul(100 + (1_000_000 << 32));
+    let hi = ((v >> 16) & 0x0000_00FF_0000_00FF).wrapping_mul(1 + (10_000 << 32));
+    (lo.wrapping_add(hi)) >> 32
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+mod simd {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    /// Returns a 16 bit mask with a bit set for every digit in `chunk`.
+    #[inline]
+    pub(super) fn digit_mask(chunk: &[u8; 16]) -> u32 {
+        // Safety: the load is unaligned and reads exactly 16 bytes, and
+        // SSE2 is enabled at compile time
+        unsafe {
+            let v = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            // Signed compares are fine: bytes >= 0x80 are negative and
+            // fail the lower bound
+            let ge = _mm_cmpgt_epi8(v, _mm_set1_epi8(b'0' as i8 - 1));
+            let le = _mm_cmplt_epi8(v, _mm_set1_epi8(b'9' as i8 + 1));
+            _mm_movemask_epi8(_mm_and_si128(ge, le)) as u32
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod simd {
+    use core::arch::aarch64::*;
+
+    /// Returns a 16 bit mask with a bit set for every digit in `chunk`.
+    #[inline]
+    pub(super) fn digit_mask(chunk: &[u8; 16]) -> u32 {
+        // Safety: the load reads exactly 16 bytes and NEON is enabled at
+        // compile time
+        unsafe {
+            let v = vld1q_u8(chunk.as_ptr());
+            let ge = vcgeq_u8(v, vdupq_n_u8(b'0'));
+            let le = vcleq_u8(v, vdupq_n_u8(b'9'));
+            // Each lane is 0x00 or 0xFF, narrow to 4 bits per lane
+            let m = vandq_u8(ge, le);
+            let nibbles = vshrn_n_u16(vreinterpretq_u16_u8(m), 4);
+            let bits = vget_lane_u64(vreinterpret_u64_u8(nibbles), 0);
+            let mut mask = 0;
+            for i in 0..16 {
+                mask |= (((bits >> (i * 4)) & 1) as u32) << i;
+            }
+            mask
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ),
+    all(target_arch = "aarch64", target_feature = "neon")
+)))]
+mod simd {
+    pub(super) use super::digit_mask_scalar as digit_mask;
+}
+
+/// Returns a 16 bit mask with a bit set for every digit in `chunk`.
+#[cfg_attr(
+    any(
+        all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "sse2"
+        ),
+        all(target_arch = "aarch64", target_feature = "neon")
+    ),
+    allow(dead_code)
+)]
+#[inline]
+fn digit_mask_scalar(chunk: &[u8; 16]) -> u32 {
+    chunk
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.is_ascii_digit())
+        .fold(0, |mask, (i, _)| mask | 1 << i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digit_mask_matches_scalar() {
+        for b in 0..=255u8 {
+            for i in 0..16 {
+                let mut chunk = *b"0123456789012345";
+                chunk[i] = b;
+                assert_eq!(digit_mask_scalar(&chunk), simd::digit_mask(&chunk));
+            }
+        }
+    }
+
+    #[test]
+    fn digit_run_matches_scalar() {
+        let mut buf = [b'7'; 64];
+        for len in 0..buf.len() {
+            for stop in 0..=len {
+                for &c in &[b':', b'e', b'/', b'a', 0x80, 0xB9, 0] {
+                    buf[..len].fill(b'7');
+                    if stop < len {
+                        buf[stop] = c;
+                    }
+                    let s = &buf[..len];
+                    assert_eq!(digit_run_scalar(s), digit_run(s), "{:?}", s);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_number_matches_std() {
+        let max = i64::MAX as u64;
+        for s in &[
+            "",
+            "0",
+            "7",
+            "12345678",
+            "123456789",
+            "99999999",
+            "000000000000000001",
+            "999999999999999999",
+            "9223372036854775807",
+            "9223372036854775808",
+            "18446744073709551615",
+            "18446744073709551616",
+            "00000000000000000000000000042",
+        ] {
+            // Both with and without enough trailing bytes for the vector path
+            for tail in &["", ":", "e1:abcdefghijklmnopqrst"] {
+                let buf = format!("{}{}", s, tail);
+                let expected = s.parse::<u64>().ok().filter(|&n| n <= max);
+                let expected = expected.or(if s.is_empty() { Some(0) } else { None });
+                let res = parse_number(buf.as_bytes(), max);
+                assert_eq!(expected, res.ok().map(|(n, _)| n), "{:?}", buf);
+                if let Ok((_, run)) = res {
+                    assert_eq!(s.len(), run);
+                }
+            }
+        }
+
+        for n in (0..100_000_000u64).step_by(9_973).chain(Some(99_999_999)) {
+            let buf = format!("{}e0000000000000000", n);
+            assert_eq!(Ok((n, buf.len() - 17)), parse_number(buf.as_bytes(), max));
+        }
+
+        let max32 = u64::from(u32::MAX);
+        assert_eq!(
+            Ok((u64::from(u32::MAX), 10)),
+            parse_number(b"4294967295", max32)
+        );
+        assert_eq!(Err(9), parse_number(b"4294967296:", max32));
+        assert_eq!(Err(10), parse_number(b"42949672950:0000000000000", max32));
+        assert_eq!(Err(18), parse_number(b"9223372036854775808", max));
+    }
+
+    #[test]
+    fn parse_8_all_positions() {
+        for pos in 0..8 {
+            for d in 0..10u8 {
+                let mut chunk = [b'0'; 8];
+                chunk[pos] = b'0' + d;
+                let expected = u64::from(d) * 10u64.pow(7 - pos as u32);
+                assert_eq!(expected, parse_8(u64::from_le_bytes(chunk)));
+            }
+        }
+        assert_eq!(12_345_678, parse_8(u64::from_le_bytes(*b"12345678")));
+        assert_eq!(99_999_999, parse_8(u64::from_le_bytes(*b"99999999")));
+    }
+}
@@ -0,0 +1,448 @@
+//! Declarative validation of bencode documents.
+//!
+//! A `Schema` describes the expected structure of a value: its type, the
+//! range of integers, the length of byte strings and lists, the schema of
+//! list elements and the required and optional keys of dictionaries.
+//! Validating a `Decoder` reports every violation with its path and byte
+//! offset rather than stopping at the first one.
+//!
+//! # Examples
+//!
+//! ```
+//! use ben::schema::{DictSchema, Schema};
+//! use ben::{Decoder, Parser};
+//!
+//! let mut args = DictSchema::new();
+//! args.required("id", Schema::bytes_exact(20));
+//! args.optional("port", Schema::Int(1..=65535));
+//!
+//! let mut query = DictSchema::new();
+//! query.required("a", Schema::Dict(args));
+//! query.required("q", Schema::bytes());
+//! query.required("t", Schema::bytes());
+//! query.required("y", Schema::bytes_exact(1));
+//! let schema = Schema::Dict(query);
+//!
+//! let parser = &mut Parser::new();
+//! let msg = parser
+//!     .parse::<Decoder>(b"d1:ad2:id3:abc4:porti0ee1:q4:ping1:y1:qe")
+//!     .unwrap();
+//! let errors = schema.validate(&msg);
+//! assert_eq!(3, errors.len());
+//! assert_eq!(".a.id at 11: length 3 not in 20..=20", errors[0].to_string());
+//! assert_eq!(".a.port at 21: 0 not in 1..=65535", errors[1].to_string());
+//! assert_eq!(".t at 0: missing", errors[2].to_string());
+//! ```
+
+use crate::decode::Decoder;
+use crate::token::TokenKind;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeInclusive;
+use core::str;
+
+/// Expected structure of a bencode value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Any value.
+    Any,
+    /// An integer in the range.
+    Int(RangeInclusive<i64>),
+    /// A byte string with a length in the range.
+    Bytes(RangeInclusive<usize>),
+    /// A list with a length in the range whose elements match the schema.
+    List(Box<Schema>, RangeInclusive<usize>),
+    /// A dictionary.
+    Dict(DictSchema),
+}
+
+impl Schema {
+    /// Any integer.
+    pub fn int() -> Self {
+        Schema::Int(i64::MIN..=i64::MAX)
+    }
+
+    /// Any byte string.
+    pub fn bytes() -> Self {
+        Schema::Bytes(0..=usize::MAX)
+    }
+
+    /// A byte string of exactly `len` bytes, e.g. 20 for info hashes and
+    /// node IDs.
+    pub fn bytes_exact(len: usize) -> Self {
+        Schema::Bytes(len..=len)
+    }
+
+    /// A list of any length whose elements match `elem`.
+    pub fn list(elem: Schema) -> Self {
+        Schema::List(Box::new(elem), 0..=usize::MAX)
+    }
+
+    /// Validate `value` against this schema and return all violations. The
+    /// value is valid if none are returned.
+    pub fn validate(&self, value: &Decoder<'_, '_>) -> Vec<Violation> {
+        let mut errors = Vec::new();
+        self.check(value, &mut Vec::new(), &mut errors);
+        errors
+    }
+
+    /// Returns true if `value` matches this schema.
+    pub fn is_valid(&self, value: &Decoder<'_, '_>) -> bool {
+        self.validate(value).is_empty()
+    }
+
+    fn check(&self, value: &Decoder<'_, '_>, path: &mut Vec<Segment>, errors: &mut Vec<Violation>) {
+        let mut fail = |reason| {
+            errors.push(Violation {
+                path: path.clone(),
+                pos: value.pos(),
+                reason,
+            })
+        };
+
+        let expected = match self {
+            Schema::Any => return,
+            Schema::Int(range) => match value.as_int() {
+                Some(n) if range.contains(&n) => return,
+                Some(n) => {
+                    return fail(Reason::OutOfRange {
+                        value: n,
+                        range: range.clone(),
+                    })
+                }
+                None => TokenKind::Int,
+            },
+            Schema::Bytes(range) => match value.as_bytes() {
+                Some(b) => return check_len(b.len(), range, fail),
+                None => TokenKind::ByteStr,
+            },
+            Schema::List(elem, range) => match value.as_list() {
+                Some(list) => {
+                    check_len(list.len(), range, fail);
+                    for (i, v) in list.iter().enumerate() {
+                        path.push(Segment::Index(i));
+                        elem.check(&v, path, errors);
+                        path.pop();
+                    }
+                    return;
+                }
+                None => TokenKind::List,
+            },
+            Schema::Dict(schema) => match value.as_dict() {
+                Some(_) => return schema.check(value, path, errors),
+                None => TokenKind::Dict,
+            },
+        };
+        fail(Reason::Type {
+            expected,
+            found: value.token().kind(),
+        })
+    }
+}
+
+fn check_len<F: FnMut(Reason)>(len: usize, range: &RangeInclusive<usize>, mut fail: F) {
+    if !range.contains(&len) {
+        fail(Reason::Length {
+            len,
+            range: range.clone(),
+        })
+    }
+}
+
+/// Expected keys of a dictionary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DictSchema {
+    keys: BTreeMap<Vec<u8>, (Schema, bool)>,
+    deny_unknown: bool,
+}
+
+impl DictSchema {
+    /// Create a schema for a dictionary with any keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require `key` with a value matching `schema`.
+    pub fn required<K: AsRef<[u8]>>(&mut self, key: K, schema: Schema) {
+        self.keys.insert(key.as_ref().to_vec(), (schema, true));
+    }
+
+    /// Allow `key`, whose value must match `schema` if present.
+    pub fn optional<K: AsRef<[u8]>>(&mut self, key: K, schema: Schema) {
+        self.keys.insert(key.as_ref().to_vec(), (schema, false));
+    }
+
+    /// Report keys which are neither required nor optional. By default
+    /// they are allowed, as most BitTorrent messages are extensible.
+    pub fn deny_unknown(&mut self, deny_unknown: bool) {
+        self.deny_unknown = deny_unknown;
+    }
+
+    fn check(&self, value: &Decoder<'_, '_>, path: &mut Vec<Segment>, errors: &mut Vec<Violation>) {
+        let dict = match value.as_dict() {
+            Some(dict) => dict,
+            None => return,
+        };
+
+        for (k, v) in dict.iter() {
+            let key = k.as_raw_bytes();
+            path.push(Segment::Key(key.to_vec()));
+            match self.keys.get(key) {
+                Some((schema, _)) => schema.check(&v, path, errors),
+                None if self.deny_unknown => errors.push(Violation {
+                    path: path.clone(),
+                    pos: k.pos(),
+                    reason: Reason::Unknown,
+                }),
+                None => {}
+            }
+            path.pop();
+        }
+
+        for (key, (_, required)) in &self.keys {
+            if *required && dict.get(key).is_none() {
+                let mut path = path.clone();
+                path.push(Segment::Key(key.clone()));
+                errors.push(Violation {
+                    path,
+                    pos: dict.pos(),
+                    reason: Reason::Missing,
+                });
+            }
+        }
+    }
+}
+
+/// A step in the path to a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Dictionary key.
+    Key(Vec<u8>),
+    /// List index.
+    Index(usize),
+}
+
+/// Why a value does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The value has the wrong type.
+    Type {
+        expected: TokenKind,
+        found: TokenKind,
+    },
+    /// The integer is outside the allowed range.
+    OutOfRange {
+        value: i64,
+        range: RangeInclusive<i64>,
+    },
+    /// The byte string or list length is outside the allowed range.
+    Length {
+        len: usize,
+        range: RangeInclusive<usize>,
+    },
+    /// A required key is missing. The path ends with the missing key and
+    /// the position is that of the dictionary.
+    Missing,
+    /// The key is not declared and the dictionary denies unknown keys.
+    Unknown,
+}
+
+/// A value that does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Path from the validated value to the offending one.
+    pub path: Vec<Segment>,
+    /// Position of the offending value in the source buffer.
+    pub pos: usize,
+    /// Why the value does not match.
+    pub reason: Reason,
+}
+
+/// Writes `bytes` as UTF-8, escaping invalid sequences as `\\xNN`.
+fn write_escaped(f: &mut fmt::Formatter<'_>, mut bytes: &[u8]) -> fmt::Result {
+    loop {
+        match str::from_utf8(bytes) {
+            Ok(s) => return f.write_str(s),
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                f.write_str(str::from_utf8(valid).unwrap_or_default())?;
+                let invalid = e.error_len().unwrap_or(rest.len());
+                for b in &rest[..invalid] {
+                    write!(f, "\\x{:02x}", b)?;
+                }
+                bytes = &rest[invalid..];
+            }
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(".")?;
+        }
+        for segment in &self.path {
+            match segment {
+                Segment::Key(key) => {
+                    f.write_str(".")?;
+                    write_escaped(f, key)?;
+                }
+                Segment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        write!(f, " at {}: ", self.pos)?;
+        match &self.reason {
+            Reason::Type { expected, found } => {
+                write!(f, "expected {:?}, found {:?}", expected, found)
+            }
+            Reason::OutOfRange { value, range } => {
+                write!(f, "{} not in {}..={}", value, range.start(), range.end())
+            }
+            Reason::Length { len, range } => {
+                write!(
+                    f,
+                    "length {} not in {}..={}",
+                    len,
+                    range.start(),
+                    range.end()
+                )
+            }
+            Reason::Missing => f.write_str("missing"),
+            Reason::Unknown => f.write_str("unknown key"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn validate(schema: &Schema, s: &[u8]) -> Vec<Violation> {
+        let parser = &mut Parser::new();
+        let decoder = parser.parse::<Decoder>(s).unwrap();
+        schema.validate(&decoder)
+    }
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(k.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn scalars() {
+        assert!(validate(&Schema::Any, b"le").is_empty());
+        assert!(validate(&Schema::Int(0..=10), b"i10e").is_empty());
+        assert!(validate(&Schema::bytes_exact(3), b"3:abc").is_empty());
+
+        assert_eq!(
+            vec![Violation {
+                path: vec![],
+                pos: 1,
+                reason: Reason::OutOfRange {
+                    value: 11,
+                    range: 0..=10
+                },
+            }],
+            validate(&Schema::Int(0..=10), b"i11e")
+        );
+        assert_eq!(
+            vec![Violation {
+                path: vec![],
+                pos: 0,
+                reason: Reason::Type {
+                    expected: TokenKind::ByteStr,
+                    found: TokenKind::List,
+                },
+            }],
+            validate(&Schema::bytes(), b"le")
+        );
+    }
+
+    #[test]
+    fn list_elements() {
+        let schema = Schema::List(Box::new(Schema::bytes_exact(2)), 1..=3);
+        assert!(validate(&schema, b"l2:ab2:cde").is_empty());
+
+        let errors = validate(&schema, b"l2:ab1:ci1e2:dee");
+        assert_eq!(3, errors.len());
+        assert_eq!(
+            Violation {
+                path: vec![],
+                pos: 0,
+                reason: Reason::Length {
+                    len: 4,
+                    range: 1..=3
+                },
+            },
+            errors[0]
+        );
+        assert_eq!(vec![Segment::Index(1)], errors[1].path);
+        assert_eq!(7, errors[1].pos);
+        assert_eq!(vec![Segment::Index(2)], errors[2].path);
+        assert_eq!(9, errors[2].pos);
+        assert_eq!(
+            "[2] at 9: expected ByteStr, found Int",
+            errors[2].to_string()
+        );
+    }
+
+    #[test]
+    fn dict_keys() {
+        let mut inner = DictSchema::new();
+        inner.required("x", Schema::int());
+        inner.deny_unknown(true);
+
+        let mut outer = DictSchema::new();
+        outer.required("a", Schema::Dict(inner));
+        outer.required("b", Schema::bytes());
+        outer.optional("c", Schema::list(Schema::int()));
+        let schema = Schema::Dict(outer);
+
+        assert!(validate(&schema, b"d1:ad1:xi1ee1:b0:e").is_empty());
+        assert!(validate(&schema, b"d1:ad1:xi1ee1:b0:1:cli1eee").is_empty());
+
+        let errors = validate(&schema, b"d1:ad1:y0:e1:cl0:ee");
+        assert_eq!(
+            vec![
+                Violation {
+                    path: vec![key("a"), key("y")],
+                    pos: 7,
+                    reason: Reason::Unknown,
+                },
+                Violation {
+                    path: vec![key("a"), key("x")],
+                    pos: 4,
+                    reason: Reason::Missing,
+                },
+                Violation {
+                    path: vec![key("c"), Segment::Index(0)],
+                    pos: 17,
+                    reason: Reason::Type {
+                        expected: TokenKind::Int,
+                        found: TokenKind::ByteStr,
+                    },
+                },
+                Violation {
+                    path: vec![key("b")],
+                    pos: 0,
+                    reason: Reason::Missing,
+                },
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn display_path() {
+        let violation = Violation {
+            path: vec![Segment::Key(b"a\xffb".to_vec()), Segment::Index(3)],
+            pos: 5,
+            reason: Reason::Missing,
+        };
+        assert_eq!(".a\\xffb[3] at 5: missing", violation.to_string());
+    }
+}
@@ -0,0 +1,448 @@
+//! Persisting parsed token tapes.
+//!
+//! Parsing large documents at every start of a process can be avoided by
+//! saving the token tape next to the source file and loading it back later.
+//! The saved tape records the length and SHA-256 checksum of the buffer it
+//! was parsed from. `Tape::load` checks both and validates the structure
+//! of the tape against the buffer before any `Decoder` is handed out.
+//!
+//! # Examples
+//!
+//! ```
+//! use ben::tape::{self, Tape};
+//! use ben::{Decoder, Parser};
+//!
+//! let buf = b"d1:ai1e1:bl1:xee";
+//! let parser = &mut Parser::new();
+//! let saved = tape::save(&parser.parse::<Decoder>(buf).unwrap());
+//!
+//! let tape = Tape::load(&saved, buf).unwrap();
+//! let dict = tape.decoder().into_dict().unwrap();
+//! assert_eq!(Some(1), dict.get_int("a"));
+//! ```
+
+use crate::decode::{Decode, Decoder};
+use crate::error::{Error, Result};
+use crate::token::{Token, TokenKind};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8; 4] = b"BTAP";
+const VERSION: u32 = 1;
+const CHECKSUM_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 4 + 8 + CHECKSUM_LEN + 4;
+const TOKEN_LEN: usize = 1 + 4 * 4;
+
+/// Serialize the tape of `decoder` along with the checksum of its source
+/// buffer.
+pub fn save(decoder: &Decoder<'_, '_>) -> Vec<u8> {
+    let tokens = decoder.tape();
+    let mut out = Vec::with_capacity(HEADER_LEN + tokens.len() * TOKEN_LEN);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(decoder.buf.len() as u64).to_le_bytes());
+    out.extend_from_slice(&Sha256::digest(decoder.buf));
+    out.extend_from_slice(&(tokens.len() as u32).to_le_bytes());
+    for token in tokens {
+        out.push(match token.kind {
+            TokenKind::Dict => 0,
+            TokenKind::List => 1,
+            TokenKind::ByteStr => 2,
+            TokenKind::Int => 3,
+        });
+        for n in &[token.start, token.end, token.children, token.next] {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// A token tape loaded back for a buffer.
+#[derive(Debug)]
+pub struct Tape<'a> {
+    buf: &'a [u8],
+    tokens: Vec<Token>,
+}
+
+impl<'a> Tape<'a> {
+    /// Load a tape saved with `save` for `buf`.
+    ///
+    /// Fails if the tape is malformed, was saved for a buffer with different
+    /// contents or is inconsistent with `buf`.
+    pub fn load(data: &[u8], buf: &'a [u8]) -> Result<Self> {
+        let tape = Self::load_unchecked_checksum(data, buf)?;
+        if data[16..16 + CHECKSUM_LEN] != Sha256::digest(buf)[..] {
+            return Err(Error::Invalid {
+                reason: "Tape checksum does not match buffer",
+                pos: 16,
+            });
+        }
+        Ok(tape)
+    }
+
+    /// Load a tape saved with `save` for `buf` without verifying the
+    /// checksum of `buf`.
+    ///
+    /// This skips hashing the whole buffer. The tape is still validated
+    /// against `buf` so decoding stays memory safe, but a buffer which
+    /// changed since the tape was saved may decode to wrong values.
+    pub fn load_unchecked_checksum(data: &[u8], buf: &'a [u8]) -> Result<Self> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::Eof);
+        }
+        if &data[..4] != MAGIC {
+            return Err(Error::Invalid {
+                reason: "Not a token tape",
+                pos: 0,
+            });
+        }
+        if read_u32(data, 4) != VERSION {
+            return Err(Error::Invalid {
+                reason: "Unsupported token tape version",
+                pos: 4,
+            });
+        }
+        if u64::from_le_bytes(data[8..16].try_into().unwrap()) != buf.len() as u64 {
+            return Err(Error::Invalid {
+                reason: "Tape buffer length does not match buffer",
+                pos: 8,
+            });
+        }
+
+        let count = read_u32(data, HEADER_LEN - 4) as usize;
+        let body = &data[HEADER_LEN..];
+        if body.len() / TOKEN_LEN < count {
+            return Err(Error::Eof);
+        }
+        if body.len() != count * TOKEN_LEN {
+            return Err(Error::Invalid {
+                reason: "Extra bytes at the end",
+                pos: HEADER_LEN + count * TOKEN_LEN,
+            });
+        }
+
+        let mut tokens = Vec::with_capacity(count);
+        for (i, raw) in body.chunks_exact(TOKEN_LEN).enumerate() {
+            let kind = match raw[0] {
+                0 => TokenKind::Dict,
+                1 => TokenKind::List,
+                2 => TokenKind::ByteStr,
+                3 => TokenKind::Int,
+                _ => {
+                    return Err(Error::Invalid {
+                        reason: "Invalid token kind",
+                        pos: token_pos(i),
+                    })
+                }
+            };
+            tokens.push(Token::with_size(
+                kind,
+                read_u32(raw, 1),
+                read_u32(raw, 5),
+                read_u32(raw, 9),
+                read_u32(raw, 13),
+            ));
+        }
+
+        validate(&tokens, buf).map_err(|i| Error::Invalid {
+            reason: "Tape is inconsistent with buffer",
+            pos: token_pos(i),
+        })?;
+
+        Ok(Tape { buf, tokens })
+    }
+
+    /// Returns the tokens of this tape.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Returns a decoder for the root value of this tape.
+    pub fn decoder(&self) -> Decoder<'a, '_> {
+        // Tapes are never empty (ensured by validate)
+        Decoder::new(self.buf, &self.tokens).unwrap()
+    }
+
+    /// Decode the root value of this tape.
+    pub fn decode<'p, T: Decode<'a, 'p>>(&'p self) -> Result<T> {
+        T::decode(self.decoder())
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn token_pos(index: usize) -> usize {
+    HEADER_LEN + index * TOKEN_LEN
+}
+
+/// Checks that `tokens` form a tape the parser could have produced for
+/// `buf`. Returns the index of the first offending token.
+///
+/// Every token has to be in bounds of `buf` and every subtree in bounds of
+/// the tape, which is what the decoder relies on for memory safety. The
+/// delimiters around each token and the integer digits are checked too, so
+/// decoding never panics.
+fn validate(tokens: &[Token], buf: &[u8]) -> core::result::Result<(), usize> {
+    match tokens.first() {
+        Some(root) if root.next as usize == tokens.len() => {}
+        _ => return Err(0),
+    }
+
+    for (i, token) in tokens.iter().enumerate() {
+        let (start, end, next) = (
+            token.start as usize,
+            token.end as usize,
+            token.next as usize,
+        );
+        if start > end || end > buf.len() || next == 0 || i + next > tokens.len() {
+            return Err(i);
+        }
+
+        let valid = match token.kind {
+            TokenKind::Int => {
+                next == 1
+                    && token.children == 0
+                    && start > 0
+                    && buf[start - 1] == b'i'
+                    && buf.get(end) == Some(&b'e')
+                    && is_int(&buf[start..end])
+            }
+            TokenKind::ByteStr => {
+                next == 1 && token.children == 0 && start >= 2 && buf[start - 1] == b':'
+            }
+            TokenKind::List | TokenKind::Dict => {
+                let open = if token.kind == TokenKind::List {
+                    b'l'
+                } else {
+                    b'd'
+                };
+                end - start >= 2
+                    && buf[start] == open
+                    && buf[end - 1] == b'e'
+                    && validate_children(tokens, i)
+            }
+        };
+        if !valid {
+            return Err(i);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the direct children of container `tokens[index]` exactly
+/// fill its subtree and lie in order inside its bounds.
+fn validate_children(tokens: &[Token], index: usize) -> bool {
+    let parent = &tokens[index];
+    let is_dict = parent.kind == TokenKind::Dict;
+    let subtree_end = index + parent.next as usize;
+    let content_end = parent.end as usize - 1;
+
+    let mut cursor = parent.start as usize + 1;
+    let mut count = 0;
+    let mut j = index + 1;
+    while j < subtree_end {
+        let child = &tokens[j];
+        let (start, end, next) = (
+            child.start as usize,
+            child.end as usize,
+            child.next as usize,
+        );
+        if next == 0 || j + next > subtree_end || start < cursor {
+            return false;
+        }
+        let is_key = is_dict && count % 2 == 0;
+        if is_key && child.kind != TokenKind::ByteStr {
+            return false;
+        }
+        // Include the closing 'e' of integers
+        let end = if child.kind == TokenKind::Int {
+            end + 1
+        } else {
+            end
+        };
+        if end > content_end {
+            return false;
+        }
+        cursor = end;
+        count += 1;
+        j += next;
+    }
+
+    let expected = parent.children as usize * if is_dict { 2 } else { 1 };
+    j == subtree_end && count == expected
+}
+
+/// Checks integer digits the same way the parser does.
+fn is_int(digits: &[u8]) -> bool {
+    // Negative values go one further, down to `i64::MIN`
+    let (digits, max) = match digits.strip_prefix(b"-") {
+        Some(digits) => (digits, i64::MAX as u64 + 1),
+        None => (digits, i64::MAX as u64),
+    };
+    !digits.is_empty()
+        && digits
+            .iter()
+            .try_fold(0u64, |n, &c| {
+                if c.is_ascii_digit() {
+                    n.checked_mul(10)?
+                        .checked_add(u64::from(c - b'0'))
+                        .filter(|&n| n <= max)
+                } else {
+                    None
+                }
+            })
+            .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+
+    const DOC: &[u8] = b"d1:ad1:bi-12e1:cli1e0:ee1:x3:abce";
+
+    fn saved() -> Vec<u8> {
+        let parser = &mut Parser::new();
+        save(&parser.parse::<Decoder>(DOC).unwrap())
+    }
+
+    #[test]
+    fn round_trip() {
+        let parser = &mut Parser::new();
+        parser.parse::<Decoder>(DOC).unwrap();
+
+        let data = saved();
+        assert_eq!(HEADER_LEN + parser.tokens().len() * TOKEN_LEN, data.len());
+
+        let tape = Tape::load(&data, DOC).unwrap();
+        assert_eq!(parser.tokens(), tape.tokens());
+
+        let dict = tape.decode::<crate::decode::Dict>().unwrap();
+        assert_eq!(Some(&b"abc"[..]), dict.get_bytes("x"));
+        assert_eq!(Some(-12), dict.get_dict("a").unwrap().get_int("b"));
+    }
+
+    #[test]
+    fn round_trip_int_limits() {
+        let parser = &mut Parser::new();
+        for doc in [
+            &b"i-9223372036854775808e"[..],
+            b"i9223372036854775807e",
+            b"li-9223372036854775808ee",
+        ] {
+            let data = save(&parser.parse::<Decoder>(doc).unwrap());
+            let tape = Tape::load(&data, doc).unwrap();
+            assert_eq!(parser.tokens(), tape.tokens());
+        }
+
+        assert!(!is_int(b"9223372036854775808"));
+        assert!(!is_int(b"-9223372036854775809"));
+    }
+
+    #[test]
+    fn save_subtree() {
+        let parser = &mut Parser::new();
+        let dict = parser.parse::<crate::decode::Dict>(DOC).unwrap();
+        let data = save(&dict.get("a").unwrap());
+
+        let tape = Tape::load(&data, DOC).unwrap();
+        assert_eq!(7, tape.tokens().len());
+        assert_eq!(Some(-12), tape.decoder().as_dict().unwrap().get_int("b"));
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let data = saved();
+        let mut other = DOC.to_vec();
+        other[DOC.len() - 2] = b'x';
+
+        assert_eq!(
+            Err(Error::Invalid {
+                reason: "Tape checksum does not match buffer",
+                pos: 16,
+            }),
+            Tape::load(&data, &other).map(|_| ())
+        );
+        assert!(Tape::load_unchecked_checksum(&data, &other).is_ok());
+    }
+
+    #[test]
+    fn length_mismatch() {
+        let data = saved();
+        assert_eq!(
+            Err(Error::Invalid {
+                reason: "Tape buffer length does not match buffer",
+                pos: 8,
+            }),
+            Tape::load(&data, &DOC[..DOC.len() - 1]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let data = saved();
+        for i in 0..data.len() {
+            assert!(Tape::load(&data[..i], DOC).is_err());
+        }
+    }
+
+    #[test]
+    fn corrupted_tokens() {
+        let data = saved();
+        let mut other = DOC.to_vec();
+        other[5] = b'x';
+
+        // Flipping any single byte of the tokens must never produce a
+        // tape which passes validation and then misbehaves
+        for i in HEADER_LEN..data.len() {
+            for &b in &[0x00, 0x01, 0x02, 0x03, 0x7f, 0xff] {
+                let mut data = data.clone();
+                data[i] = b;
+                for buf in &[DOC, &other[..]] {
+                    if let Ok(tape) = Tape::load_unchecked_checksum(&data, buf) {
+                        walk(tape.decoder());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_bounds() {
+        let parser = &mut Parser::new();
+        let data = save(&parser.parse::<Decoder>(b"3:abc").unwrap());
+
+        let mut bad = data.clone();
+        // Token end
+        bad[HEADER_LEN + 5..HEADER_LEN + 9].copy_from_slice(&6u32.to_le_bytes());
+        assert_eq!(
+            Err(Error::Invalid {
+                reason: "Tape is inconsistent with buffer",
+                pos: HEADER_LEN,
+            }),
+            Tape::load_unchecked_checksum(&bad, b"3:abc").map(|_| ())
+        );
+    }
+
+    fn walk(decoder: Decoder<'_, '_>) {
+        let _ = format!("{:?}", decoder);
+        let _ = decoder.as_raw_bytes();
+        for i in 0..decoder.tape().len() {
+            let _ = decoder.at(i).unwrap().as_int();
+        }
+        if let Some(dict) = decoder.as_dict() {
+            for (k, v) in dict.iter() {
+                let _ = k.as_bytes();
+                walk(v);
+            }
+        } else if let Some(list) = decoder.as_list() {
+            for v in list.iter() {
+                walk(v);
+            }
+        }
+    }
+}
@@ -1,13 +1,20 @@
-use std::fmt;
-use std::ops::Range;
+use core::fmt;
+use core::ops::Range;
 
-#[derive(Clone, PartialEq)]
+/// A single entry of the token tape produced by the parser.
+///
+/// The tape stores values in pre-order: a list or dictionary is followed by
+/// the tokens of its children, dictionary keys and values alternating.
+///
+/// Tokens are only created by the parser. Use `Token::default()` to fill a
+/// buffer for `SliceParser`.
+#[derive(Clone, Copy, PartialEq)]
 pub struct Token {
-    pub kind: TokenKind,
-    pub start: u32,
-    pub end: u32,
-    pub children: u32,
-    pub next: u32,
+    pub(crate) kind: TokenKind,
+    pub(crate) start: u32,
+    pub(crate) end: u32,
+    pub(crate) children: u32,
+    pub(crate) next: u32,
 }
 
 impl fmt::Debug for Token {
@@ -16,12 +23,32 @@ impl fmt::Debug for Token {
     }
 }
 
+impl Default for Token {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
 impl Token {
-    pub fn new(kind: TokenKind, start: u32, end: u32) -> Self {
+    pub(crate) const EMPTY: Token = Token {
+        kind: TokenKind::Int,
+        start: 0,
+        end: 0,
+        children: 0,
+        next: 1,
+    };
+
+    pub(crate) fn new(kind: TokenKind, start: u32, end: u32) -> Self {
         Self::with_size(kind, start, end, 0, 1)
     }
 
-    pub fn with_size(kind: TokenKind, start: u32, end: u32, children: u32, next: u32) -> Self {
+    pub(crate) fn with_size(
+        kind: TokenKind,
+        start: u32,
+        end: u32,
+        children: u32,
+        next: u32,
+    ) -> Self {
         Self {
             kind,
             start,
@@ -31,15 +58,36 @@ impl Token {
         }
     }
 
+    /// Returns the type of value this token represents.
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
     /// Returns this token's bounds in the original buffer.
+    ///
+    /// This covers the complete encoding of lists and dictionaries, but only
+    /// the contents of byte strings and the digits of integers.
     pub fn range(&self) -> Range<usize> {
         debug_assert!(self.end >= self.start);
 
         self.start as usize..self.end as usize
     }
+
+    /// Returns the number of direct children: elements of a list or
+    /// key-value pairs of a dictionary. Always 0 for strings and integers.
+    pub fn children(&self) -> usize {
+        self.children as usize
+    }
+
+    /// Returns the number of tokens this value spans on the tape, including
+    /// this token. The next sibling is this many tokens further.
+    pub fn subtree_len(&self) -> usize {
+        self.next as usize
+    }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+/// The type of a bencoded value.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum TokenKind {
     Dict,
     List,
@@ -0,0 +1,752 @@
+//! HTTP tracker announce and scrape responses (BEP 3, BEP 7, BEP 23, BEP 48).
+//!
+//! `AnnounceResponse` and `ScrapeResponse` are zero-copy views used by
+//! clients, `AnnounceReply` and `ScrapeReply` encode the same documents on
+//! the tracker side.
+
+use crate::decode::{Decode, Decoder, Dict, DictIter, List, ListIter};
+use crate::encode::{BytesExact, Encode, Encoder, Sink};
+use crate::error::{Error, Result};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::slice::Chunks;
+
+pub(crate) const COMPACT_V4_LEN: usize = 6;
+pub(crate) const COMPACT_V6_LEN: usize = 18;
+
+/// A tracker's response to an announce request.
+#[derive(Debug)]
+pub struct AnnounceResponse<'a, 'p> {
+    /// Human readable error. If present, no other fields are set.
+    pub failure_reason: Option<&'a str>,
+
+    /// Human readable warning which doesn't fail the announce.
+    pub warning_message: Option<&'a str>,
+
+    /// Seconds the client should wait between regular announces.
+    pub interval: i64,
+
+    /// Minimum seconds between announces.
+    pub min_interval: Option<i64>,
+
+    /// Opaque id to send back on subsequent announces.
+    pub tracker_id: Option<&'a [u8]>,
+
+    /// Number of seeders.
+    pub complete: Option<i64>,
+
+    /// Number of leechers.
+    pub incomplete: Option<i64>,
+
+    /// IPv4 peers in either encoding.
+    pub peers: Peers<'a, 'p>,
+
+    /// Compact IPv6 peers (BEP 7).
+    pub peers6: Peers<'a, 'p>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for AnnounceResponse<'a, 'p> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let pos = decoder.pos();
+        let dict = Dict::decode(decoder)?;
+
+        let mut response = AnnounceResponse {
+            failure_reason: None,
+            warning_message: dict.get_str("warning message"),
+            interval: 0,
+            min_interval: dict.get_int("min interval"),
+            tracker_id: dict.get_bytes("tracker id"),
+            complete: dict.get_int("complete"),
+            incomplete: dict.get_int("incomplete"),
+            peers: Peers::Compact(&[]),
+            peers6: Peers::Compact6(&[]),
+        };
+
+        if let Some(reason) = dict.get("failure reason") {
+            response.failure_reason = Some(reason.as_str().ok_or(Error::Invalid {
+                reason: "Failure reason is not a UTF-8 string",
+                pos: reason.pos(),
+            })?);
+            return Ok(response);
+        }
+
+        response.interval = dict
+            .get_int("interval")
+            .ok_or_else(|| Error::missing("interval", pos))?;
+        if let Some(peers) = dict.get("peers") {
+            response.peers = Peers::decode_v4(peers)?;
+        }
+        if let Some(peers6) = dict.get("peers6") {
+            response.peers6 = Peers::decode_compact(peers6, COMPACT_V6_LEN)?;
+        }
+        Ok(response)
+    }
+}
+
+impl<'a, 'p> AnnounceResponse<'a, 'p> {
+    /// Returns an iterator over all IPv4 and IPv6 peers.
+    pub fn all_peers(&self) -> impl Iterator<Item = Result<Peer<'a>>> + '_ {
+        self.peers.iter().chain(self.peers6.iter())
+    }
+}
+
+/// The peer list of an announce response.
+#[derive(Debug)]
+pub enum Peers<'a, 'p> {
+    /// Compact IPv4 peers: 4 bytes address and 2 bytes port each.
+    Compact(&'a [u8]),
+
+    /// Compact IPv6 peers: 16 bytes address and 2 bytes port each.
+    Compact6(&'a [u8]),
+
+    /// List of dictionaries with `ip`, `port` and optional `peer id` keys.
+    List(List<'a, 'p>),
+}
+
+impl<'a, 'p> Peers<'a, 'p> {
+    fn decode_v4(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        if decoder.is_list() {
+            List::decode(decoder).map(Peers::List)
+        } else {
+            Self::decode_compact(decoder, COMPACT_V4_LEN)
+        }
+    }
+
+    fn decode_compact(decoder: Decoder<'a, 'p>, len: usize) -> Result<Self> {
+        let pos = decoder.pos();
+        let bytes = <&[u8]>::decode(decoder)?;
+        if bytes.len() % len != 0 {
+            return Err(Error::Invalid {
+                reason: "Compact peers have invalid length",
+                pos,
+            });
+        }
+        if len == COMPACT_V4_LEN {
+            Ok(Peers::Compact(bytes))
+        } else {
+            Ok(Peers::Compact6(bytes))
+        }
+    }
+
+    /// Returns the number of peers.
+    pub fn len(&self) -> usize {
+        match self {
+            Peers::Compact(bytes) => bytes.len() / COMPACT_V4_LEN,
+            Peers::Compact6(bytes) => bytes.len() / COMPACT_V6_LEN,
+            Peers::List(list) => list.len(),
+        }
+    }
+
+    /// Returns true if there are no peers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets an iterator over the peers.
+    pub fn iter(&self) -> PeersIter<'a, 'p> {
+        let inner = match self {
+            Peers::Compact(bytes) => PeersIterInner::Compact(bytes.chunks(COMPACT_V4_LEN)),
+            Peers::Compact6(bytes) => PeersIterInner::Compact(bytes.chunks(COMPACT_V6_LEN)),
+            Peers::List(list) => PeersIterInner::List(list.iter()),
+        };
+        PeersIter { inner }
+    }
+}
+
+/// Iterator over the peers of an announce response.
+pub struct PeersIter<'a, 'p> {
+    inner: PeersIterInner<'a, 'p>,
+}
+
+enum PeersIterInner<'a, 'p> {
+    Compact(Chunks<'a, u8>),
+    List(ListIter<'a, 'p>),
+}
+
+impl<'a, 'p> Iterator for PeersIter<'a, 'p> {
+    type Item = Result<Peer<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            PeersIterInner::Compact(chunks) => {
+                let addr = decode_compact_addr(chunks.next()?);
+                Some(Ok(Peer {
+                    addr: PeerAddr::Ip(addr),
+                    peer_id: None,
+                }))
+            }
+            PeersIterInner::List(iter) => Some(decode_peer_dict(iter.next()?)),
+        }
+    }
+}
+
+/// A peer returned by a tracker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peer<'a> {
+    /// Address of the peer.
+    pub addr: PeerAddr<'a>,
+
+    /// Peer id, only present in non-compact responses.
+    pub peer_id: Option<&'a [u8]>,
+}
+
+/// Address of a peer returned by a tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerAddr<'a> {
+    /// An IP address and port.
+    Ip(SocketAddr),
+
+    /// A DNS name and port. Only non-compact responses carry these, as the
+    /// `ip` of a peer may be a host name.
+    Host(&'a str, u16),
+}
+
+impl PeerAddr<'_> {
+    /// Returns the port of the peer.
+    pub fn port(&self) -> u16 {
+        match *self {
+            PeerAddr::Ip(addr) => addr.port(),
+            PeerAddr::Host(_, port) => port,
+        }
+    }
+
+    /// Returns the socket address if the peer was given by IP address.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match *self {
+            PeerAddr::Ip(addr) => Some(addr),
+            PeerAddr::Host(..) => None,
+        }
+    }
+}
+
+impl From<SocketAddr> for PeerAddr<'_> {
+    fn from(addr: SocketAddr) -> Self {
+        PeerAddr::Ip(addr)
+    }
+}
+
+impl fmt::Display for PeerAddr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddr::Ip(addr) => addr.fmt(f),
+            PeerAddr::Host(host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+pub(crate) fn decode_compact_addr(bytes: &[u8]) -> SocketAddr {
+    let (ip, port) = bytes.split_at(bytes.len() - 2);
+    let port = u16::from_be_bytes([port[0], port[1]]);
+    if ip.len() == 4 {
+        let ip = Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]);
+        SocketAddr::V4(SocketAddrV4::new(ip, port))
+    } else {
+        let mut octets = [0; 16];
+        octets.copy_from_slice(ip);
+        SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(octets), port, 0, 0))
+    }
+}
+
+fn decode_peer_dict<'a>(decoder: Decoder<'a, '_>) -> Result<Peer<'a>> {
+    let pos = decoder.pos();
+    let dict = Dict::decode(decoder)?;
+
+    let ip = dict
+        .get_str("ip")
+        .ok_or_else(|| Error::missing("ip", pos))?;
+
+    let port = dict
+        .get_int("port")
+        .ok_or_else(|| Error::missing("port", pos))?;
+    if !(0..=i64::from(u16::MAX)).contains(&port) {
+        return Err(Error::Invalid {
+            reason: "Peer port out of range",
+            pos,
+        });
+    }
+
+    let port = port as u16;
+    let addr = match ip.parse::<IpAddr>() {
+        Ok(ip) => PeerAddr::Ip(SocketAddr::new(ip, port)),
+        Err(_) => PeerAddr::Host(ip, port),
+    };
+    Ok(Peer {
+        addr,
+        peer_id: dict.get_bytes("peer id"),
+    })
+}
+
+/// A tracker's response to a scrape request.
+#[derive(Debug)]
+pub struct ScrapeResponse<'a, 'p> {
+    /// Human readable error. If present, `files` is empty.
+    pub failure_reason: Option<&'a str>,
+
+    files: Option<Dict<'a, 'p>>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for ScrapeResponse<'a, 'p> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let pos = decoder.pos();
+        let dict = Dict::decode(decoder)?;
+
+        if let Some(reason) = dict.get_str("failure reason") {
+            return Ok(ScrapeResponse {
+                failure_reason: Some(reason),
+                files: None,
+            });
+        }
+
+        match dict.get("files") {
+            Some(files) => Ok(ScrapeResponse {
+                failure_reason: None,
+                files: Some(Dict::decode(files)?),
+            }),
+            None => Err(Error::missing("files", pos)),
+        }
+    }
+}
+
+impl<'a, 'p> ScrapeResponse<'a, 'p> {
+    /// Returns the statistics for given info-hash.
+    pub fn get(&self, info_hash: &[u8]) -> Result<Option<ScrapeFile<'a>>> {
+        match self.files.as_ref().and_then(|files| files.get(info_hash)) {
+            Some(file) => ScrapeFile::decode(file).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the number of torrents in this response.
+    pub fn len(&self) -> usize {
+        self.files.as_ref().map_or(0, Dict::len)
+    }
+
+    /// Returns true if this response has no torrents.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets an iterator over the info-hashes and their statistics.
+    pub fn iter(&self) -> ScrapeIter<'a, 'p> {
+        ScrapeIter {
+            inner: self.files.as_ref().map(Dict::iter),
+        }
+    }
+}
+
+/// Iterator over the torrents of a scrape response.
+pub struct ScrapeIter<'a, 'p> {
+    inner: Option<DictIter<'a, 'p>>,
+}
+
+impl<'a, 'p> Iterator for ScrapeIter<'a, 'p> {
+    type Item = Result<(&'a [u8], ScrapeFile<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.inner.as_mut()?.next()?;
+        Some(ScrapeFile::decode(v).map(|file| (k.as_raw_bytes(), file)))
+    }
+}
+
+/// Scrape statistics of a single torrent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrapeFile<'a> {
+    /// Number of seeders.
+    pub complete: i64,
+
+    /// Number of completed downloads.
+    pub downloaded: i64,
+
+    /// Number of leechers.
+    pub incomplete: i64,
+
+    /// Torrent name (BEP 48 extension).
+    pub name: Option<&'a str>,
+}
+
+impl<'a, 'p> Decode<'a, 'p> for ScrapeFile<'a> {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        let pos = decoder.pos();
+        let dict = Dict::decode(decoder)?;
+        let get = |key| dict.get_int(key).ok_or_else(|| Error::missing(key, pos));
+        Ok(ScrapeFile {
+            complete: get("complete")?,
+            downloaded: get("downloaded")?,
+            incomplete: get("incomplete")?,
+            name: dict.get_str("name"),
+        })
+    }
+}
+
+impl Encode for ScrapeFile<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let mut dict = enc.add_dict();
+        dict.add("complete", self.complete);
+        dict.add("downloaded", self.downloaded);
+        dict.add("incomplete", self.incomplete);
+        if let Some(name) = self.name {
+            dict.add("name", name);
+        }
+    }
+}
+
+/// A failure response to an announce or scrape request.
+pub struct Failure<'a>(pub &'a str);
+
+impl Encode for Failure<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        enc.add_dict().add("failure reason", self.0);
+    }
+}
+
+/// An announce response to encode.
+///
+/// # Examples
+///
+/// ```
+/// use ben::tracker::{AnnounceReply, AnnounceResponse, Peer, PeerAddr};
+/// use ben::{Encode, Parser};
+///
+/// let mut reply = AnnounceReply::new(1800);
+/// reply.peers.push(Peer {
+///     addr: PeerAddr::Ip("10.0.0.1:6881".parse().unwrap()),
+///     peer_id: None,
+/// });
+/// let buf = reply.encode_to_vec();
+///
+/// let parser = &mut Parser::new();
+/// let response = parser.parse::<AnnounceResponse>(&buf).unwrap();
+/// assert_eq!(1800, response.interval);
+/// assert_eq!(1, response.peers.len());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AnnounceReply<'a> {
+    /// Seconds the client should wait between regular announces.
+    pub interval: i64,
+
+    /// Minimum seconds between announces.
+    pub min_interval: Option<i64>,
+
+    /// Opaque id for the client to send back.
+    pub tracker_id: Option<&'a [u8]>,
+
+    /// Human readable warning.
+    pub warning_message: Option<&'a str>,
+
+    /// Number of seeders.
+    pub complete: Option<i64>,
+
+    /// Number of leechers.
+    pub incomplete: Option<i64>,
+
+    /// Peers to return.
+    pub peers: Vec<Peer<'a>>,
+
+    /// Use the compact peer encoding. IPv6 peers are then sent in `peers6`.
+    /// Peer ids and peers given by host name are dropped.
+    pub compact: bool,
+}
+
+impl AnnounceReply<'_> {
+    /// Create a compact reply with given interval and no peers.
+    pub fn new(interval: i64) -> Self {
+        Self {
+            interval,
+            compact: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Encode for AnnounceReply<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let mut dict = enc.add_dict();
+        if let Some(complete) = self.complete {
+            dict.add("complete", complete);
+        }
+        if let Some(incomplete) = self.incomplete {
+            dict.add("incomplete", incomplete);
+        }
+        dict.add("interval", self.interval);
+        if let Some(min_interval) = self.min_interval {
+            dict.add("min interval", min_interval);
+        }
+
+        if self.compact {
+            let addrs = self.peers.iter().filter_map(|p| p.addr.socket_addr());
+            let v4 = addrs.clone().filter(SocketAddr::is_ipv4);
+            let v6 = addrs.filter(SocketAddr::is_ipv6);
+
+            let mut bytes = dict.add_bytes_exact("peers", v4.clone().count() * COMPACT_V4_LEN);
+            for addr in v4 {
+                add_compact_addr(&mut bytes, &addr);
+            }
+            bytes.finish();
+
+            let n = v6.clone().count();
+            if n > 0 {
+                let mut bytes = dict.add_bytes_exact("peers6", n * COMPACT_V6_LEN);
+                for addr in v6 {
+                    add_compact_addr(&mut bytes, &addr);
+                }
+            }
+        } else {
+            let mut list = dict.add_list("peers");
+            for peer in &self.peers {
+                let mut p = list.add_dict();
+                match peer.addr {
+                    PeerAddr::Ip(addr) => p.add("ip", addr.ip().to_string()),
+                    PeerAddr::Host(host, _) => p.add("ip", host),
+                }
+                if let Some(peer_id) = peer.peer_id {
+                    p.add("peer id", peer_id);
+                }
+                p.add("port", i64::from(peer.addr.port()));
+            }
+        }
+
+        if let Some(tracker_id) = self.tracker_id {
+            dict.add("tracker id", tracker_id);
+        }
+        if let Some(warning) = self.warning_message {
+            dict.add("warning message", warning);
+        }
+    }
+}
+
+fn add_compact_addr<S: Sink>(bytes: &mut BytesExact<'_, S>, addr: &SocketAddr) {
+    match addr {
+        SocketAddr::V4(addr) => bytes.add(&addr.ip().octets()),
+        SocketAddr::V6(addr) => bytes.add(&addr.ip().octets()),
+    }
+    bytes.add(&addr.port().to_be_bytes());
+}
+
+/// A scrape response to encode.
+#[derive(Debug, Clone, Default)]
+pub struct ScrapeReply<'a> {
+    /// Info-hashes and their statistics, in any order.
+    pub files: Vec<(&'a [u8], ScrapeFile<'a>)>,
+}
+
+impl Encode for ScrapeReply<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let mut dict = enc.add_dict();
+        let mut files = dict.add_ordered_dict("files");
+        for (info_hash, file) in &self.files {
+            files.add(*info_hash, file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn announce_compact() {
+        let s = b"d8:completei5e10:incompletei3e8:intervali1800e5:peers12:\
+                  \x0a\x00\x00\x01\x1a\xe1\x7f\x00\x00\x01\x00\x50\
+                  6:peers618:\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x1a\xe1e";
+        let parser = &mut Parser::new();
+        let response = parser.parse::<AnnounceResponse>(s).unwrap();
+        assert_eq!(None, response.failure_reason);
+        assert_eq!(1800, response.interval);
+        assert_eq!(Some(5), response.complete);
+        assert_eq!(Some(3), response.incomplete);
+
+        let peers = response.all_peers().collect::<Result<Vec<_>>>().unwrap();
+        let addrs: Vec<_> = peers.iter().map(|p| p.addr.to_string()).collect();
+        assert_eq!(vec!["10.0.0.1:6881", "127.0.0.1:80", "[::1]:6881"], addrs);
+    }
+
+    #[test]
+    fn announce_dict_peers() {
+        let s = b"d8:intervali60e5:peersld2:ip8:10.0.0.17:peer id20:aaaaaaaaaaaaaaaaaaaa4:porti6881eed2:ip3:::14:porti1eeee";
+        let parser = &mut Parser::new();
+        let response = parser.parse::<AnnounceResponse>(&s[..]).unwrap();
+        let peers = response.peers.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            vec![
+                Peer {
+                    addr: PeerAddr::Ip("10.0.0.1:6881".parse().unwrap()),
+                    peer_id: Some(&[b'a'; 20][..]),
+                },
+                Peer {
+                    addr: PeerAddr::Ip("[::1]:1".parse().unwrap()),
+                    peer_id: None,
+                },
+            ],
+            peers
+        );
+    }
+
+    #[test]
+    fn announce_failure() {
+        let buf = Failure("torrent not registered").encode_to_vec();
+        let parser = &mut Parser::new();
+        let response = parser.parse::<AnnounceResponse>(&buf).unwrap();
+        assert_eq!(Some("torrent not registered"), response.failure_reason);
+        assert!(response.peers.is_empty());
+    }
+
+    #[test]
+    fn announce_invalid() {
+        let parser = &mut Parser::new();
+        let err = parser
+            .parse::<AnnounceResponse>(b"d5:peers0:e")
+            .unwrap_err();
+        assert_eq!(Error::missing("interval", 0), err);
+
+        let err = parser
+            .parse::<AnnounceResponse>(b"d8:intervali1e5:peers5:aaaaae")
+            .unwrap_err();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Compact peers have invalid length",
+                pos: 23,
+            },
+            err
+        );
+
+        let s = b"d8:intervali1e5:peersld2:ip8:10.0.0.14:porti-1eed2:ip8:10.0.0.24:porti1eeee";
+        let response = parser.parse::<AnnounceResponse>(s).unwrap();
+        let mut peers = response.peers.iter();
+        assert_eq!(
+            Some(Err(Error::Invalid {
+                reason: "Peer port out of range",
+                pos: 22,
+            })),
+            peers.next()
+        );
+        assert_eq!(
+            "10.0.0.2:1",
+            peers.next().unwrap().unwrap().addr.to_string()
+        );
+    }
+
+    #[test]
+    fn announce_host_peers() {
+        let s =
+            b"d8:intervali1e5:peersld2:ip11:example.com4:porti6881eed2:ip8:10.0.0.14:porti1eeee";
+        let parser = &mut Parser::new();
+        let response = parser.parse::<AnnounceResponse>(&s[..]).unwrap();
+        let peers = response.peers.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(PeerAddr::Host("example.com", 6881), peers[0].addr);
+        assert_eq!("example.com:6881", peers[0].addr.to_string());
+        assert_eq!(None, peers[0].addr.socket_addr());
+        assert_eq!(
+            Some("10.0.0.1:1".parse().unwrap()),
+            peers[1].addr.socket_addr()
+        );
+
+        let mut reply = AnnounceReply::new(1);
+        reply.peers = peers.clone();
+        reply.compact = false;
+        let buf = reply.encode_to_vec();
+        let response = parser.parse::<AnnounceResponse>(&buf).unwrap();
+        let decoded = response.peers.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(peers, decoded);
+
+        reply.compact = true;
+        let buf = reply.encode_to_vec();
+        let response = parser.parse::<AnnounceResponse>(&buf).unwrap();
+        assert_eq!(1, response.peers.len());
+    }
+
+    #[test]
+    fn announce_reply_round_trip() {
+        let peers = vec![
+            Peer {
+                addr: PeerAddr::Ip("10.0.0.1:6881".parse().unwrap()),
+                peer_id: Some(&[1; 20]),
+            },
+            Peer {
+                addr: PeerAddr::Ip("[2001:db8::1]:51413".parse().unwrap()),
+                peer_id: Some(&[2; 20]),
+            },
+        ];
+        let mut reply = AnnounceReply::new(900);
+        reply.min_interval = Some(60);
+        reply.complete = Some(1);
+        reply.incomplete = Some(2);
+        reply.tracker_id = Some(b"xyz");
+        reply.warning_message = Some("slow down");
+        reply.peers = peers.clone();
+
+        let buf = reply.encode_to_vec();
+        let parser = &mut Parser::new();
+        let response = parser.parse::<AnnounceResponse>(&buf).unwrap();
+        assert_eq!(900, response.interval);
+        assert_eq!(Some(60), response.min_interval);
+        assert_eq!(Some(&b"xyz"[..]), response.tracker_id);
+        assert_eq!(Some("slow down"), response.warning_message);
+        assert_eq!(1, response.peers.len());
+        assert_eq!(1, response.peers6.len());
+        let addrs = response
+            .all_peers()
+            .map(|p| p.unwrap().addr)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![peers[0].addr, peers[1].addr], addrs);
+
+        reply.compact = false;
+        let buf = reply.encode_to_vec();
+        let response = parser.parse::<AnnounceResponse>(&buf).unwrap();
+        assert!(response.peers6.is_empty());
+        let decoded = response.peers.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(peers, decoded);
+    }
+
+    #[test]
+    fn scrape_round_trip() {
+        let a = [0xaa; 20];
+        let b = [0x11; 20];
+        let reply = ScrapeReply {
+            files: vec![
+                (
+                    &a[..],
+                    ScrapeFile {
+                        complete: 1,
+                        downloaded: 2,
+                        incomplete: 3,
+                        name: None,
+                    },
+                ),
+                (
+                    &b[..],
+                    ScrapeFile {
+                        complete: 4,
+                        downloaded: 5,
+                        incomplete: 6,
+                        name: Some("b"),
+                    },
+                ),
+            ],
+        };
+        let buf = reply.encode_to_vec();
+
+        let parser = &mut Parser::new();
+        let response = parser.parse::<ScrapeResponse>(&buf).unwrap();
+        assert_eq!(2, response.len());
+        assert_eq!(Some(reply.files[0].1.clone()), response.get(&a).unwrap());
+        assert_eq!(None, response.get(&[0; 20]).unwrap());
+
+        // Sorted by info-hash
+        let files = response.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(&b[..], files[0].0);
+        assert_eq!(reply.files[1].1, files[0].1);
+    }
+
+    #[test]
+    fn scrape_failure() {
+        let buf = Failure("no").encode_to_vec();
+        let parser = &mut Parser::new();
+        let response = parser.parse::<ScrapeResponse>(&buf).unwrap();
+        assert_eq!(Some("no"), response.failure_reason);
+        assert!(response.is_empty());
+        assert_eq!(0, response.iter().count());
+    }
+}
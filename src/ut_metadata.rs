@@ -0,0 +1,452 @@
+//! Metadata exchange extension (BEP 9).
+//!
+//! Peers exchange the bencoded `info` dictionary in 16 KiB pieces. Each
+//! message is a bencoded dictionary, `data` messages being followed by the
+//! raw piece bytes.
+
+use crate::decode::{Decoder, Dict};
+use crate::encode::{Encode, Encoder, Sink};
+use crate::error::{Error, Result};
+use crate::extension;
+use crate::parse::Parser;
+use alloc::vec;
+use alloc::vec::Vec;
+use sha1::{Digest, Sha1};
+
+/// Name of this extension in the extension handshake.
+pub const EXTENSION_NAME: &str = "ut_metadata";
+
+/// Size of every metadata piece except the last one.
+pub const PIECE_SIZE: usize = 16 * 1024;
+
+/// Largest metadata size `MetadataAssembler::new` accepts, the same default
+/// as libtorrent's `max_metadata_size`.
+pub const MAX_METADATA_SIZE: usize = 30 * 1024 * 1024;
+
+const MSG_REQUEST: i64 = 0;
+const MSG_DATA: i64 = 1;
+const MSG_REJECT: i64 = 2;
+
+/// A ut_metadata message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message<'a> {
+    /// Request for a metadata piece.
+    Request { piece: usize },
+
+    /// A metadata piece with the total metadata size.
+    Data {
+        piece: usize,
+        total_size: usize,
+        data: &'a [u8],
+    },
+
+    /// The peer doesn't have the requested piece.
+    Reject { piece: usize },
+}
+
+impl<'a> Message<'a> {
+    /// Parse the body of an extension message, i.e. everything after the
+    /// extension id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ben::ut_metadata::Message;
+    /// use ben::Parser;
+    ///
+    /// let parser = &mut Parser::new();
+    /// let body = b"d8:msg_typei1e5:piecei0e10:total_sizei3eeabc";
+    /// let msg = Message::parse(parser, body).unwrap();
+    /// assert_eq!(
+    ///     Message::Data {
+    ///         piece: 0,
+    ///         total_size: 3,
+    ///         data: b"abc",
+    ///     },
+    ///     msg
+    /// );
+    /// ```
+    pub fn parse(parser: &mut Parser, body: &'a [u8]) -> Result<Self> {
+        let (dict, len) = parser.parse_prefix::<Dict>(body)?;
+        let trailer = &body[len..];
+
+        let msg_type = get_int(&dict, "msg_type")?;
+        let piece = get_usize(&dict, "piece")?;
+        match msg_type {
+            MSG_REQUEST | MSG_REJECT if !trailer.is_empty() => Err(Error::Invalid {
+                reason: "Extra bytes at the end",
+                pos: len,
+            }),
+            MSG_REQUEST => Ok(Message::Request { piece }),
+            MSG_REJECT => Ok(Message::Reject { piece }),
+            MSG_DATA => Ok(Message::Data {
+                piece,
+                total_size: get_usize(&dict, "total_size")?,
+                data: trailer,
+            }),
+            _ => Err(Error::Invalid {
+                reason: "Unknown ut_metadata message type",
+                pos: 0,
+            }),
+        }
+    }
+
+    /// Returns the piece index of this message.
+    pub fn piece(&self) -> usize {
+        match *self {
+            Message::Request { piece }
+            | Message::Data { piece, .. }
+            | Message::Reject { piece } => piece,
+        }
+    }
+
+    /// Write this message to `sink` as a complete extension message with
+    /// given extension id, including the trailing piece data.
+    pub fn encode_message<S: Sink>(&self, sink: &mut S, id: u8) -> Result<()> {
+        let trailer = match self {
+            Message::Data { data, .. } => data,
+            _ => &[][..],
+        };
+        extension::encode_message(sink, id, self, trailer)
+    }
+}
+
+/// Encodes the dictionary part of the message only.
+impl Encode for Message<'_> {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        let mut dict = enc.add_dict();
+        match *self {
+            Message::Request { piece } => {
+                dict.add("msg_type", MSG_REQUEST);
+                dict.add("piece", piece as i64);
+            }
+            Message::Data {
+                piece, total_size, ..
+            } => {
+                dict.add("msg_type", MSG_DATA);
+                dict.add("piece", piece as i64);
+                dict.add("total_size", total_size as i64);
+            }
+            Message::Reject { piece } => {
+                dict.add("msg_type", MSG_REJECT);
+                dict.add("piece", piece as i64);
+            }
+        }
+    }
+}
+
+fn get_int(dict: &Dict<'_, '_>, key: &'static str) -> Result<i64> {
+    dict.get_int(key)
+        .ok_or_else(|| Error::missing(key, dict.pos()))
+}
+
+fn get_usize(dict: &Dict<'_, '_>, key: &'static str) -> Result<usize> {
+    let n = get_int(dict, key)?;
+    if n < 0 {
+        return Err(Error::Invalid {
+            reason: "Value must not be negative",
+            pos: dict.get(key).as_ref().map_or(0, Decoder::pos),
+        });
+    }
+    Ok(n as usize)
+}
+
+/// Collects metadata pieces and verifies them against the info-hash.
+///
+/// # Examples
+///
+/// ```
+/// use ben::ut_metadata::MetadataAssembler;
+/// use ben::Parser;
+///
+/// # let info = b"d4:name1:ae";
+/// # let info_hash = {
+/// #     use sha1::Digest;
+/// #     sha1::Sha1::digest(info).into()
+/// # };
+/// let mut assembler = MetadataAssembler::new(info_hash, info.len()).unwrap();
+/// assert_eq!(vec![0], assembler.missing_pieces().collect::<Vec<_>>());
+/// assembler.add_piece(0, info).unwrap();
+///
+/// let parser = &mut Parser::new();
+/// let dict = assembler.info(parser).unwrap();
+/// assert_eq!(Some("a"), dict.get_str("name"));
+/// ```
+pub struct MetadataAssembler {
+    info_hash: [u8; 20],
+    buf: Vec<u8>,
+    received: Vec<bool>,
+}
+
+impl MetadataAssembler {
+    /// Create an assembler for metadata of given size, as announced in the
+    /// extension handshake's `metadata_size`.
+    ///
+    /// The size comes from a remote peer, so sizes above
+    /// `MAX_METADATA_SIZE` are rejected before anything is allocated.
+    pub fn new(info_hash: [u8; 20], total_size: usize) -> Result<Self> {
+        Self::with_limit(info_hash, total_size, MAX_METADATA_SIZE)
+    }
+
+    /// Like `new` but rejects sizes above `limit` instead of
+    /// `MAX_METADATA_SIZE`.
+    pub fn with_limit(info_hash: [u8; 20], total_size: usize, limit: usize) -> Result<Self> {
+        if total_size == 0 {
+            return Err(Error::Other("Metadata size must not be zero"));
+        }
+        if total_size > limit {
+            return Err(Error::Other("Metadata size exceeds limit"));
+        }
+        let pieces = total_size.div_ceil(PIECE_SIZE);
+        Ok(Self {
+            info_hash,
+            buf: vec![0; total_size],
+            received: vec![false; pieces],
+        })
+    }
+
+    /// Returns the total metadata size.
+    pub fn total_size(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the number of pieces.
+    pub fn num_pieces(&self) -> usize {
+        self.received.len()
+    }
+
+    /// Returns the indices of pieces which have not been received yet.
+    pub fn missing_pieces(&self) -> impl Iterator<Item = usize> + '_ {
+        self.received
+            .iter()
+            .enumerate()
+            .filter(|(_, received)| !**received)
+            .map(|(i, _)| i)
+    }
+
+    /// Returns true once all pieces have been received.
+    pub fn is_complete(&self) -> bool {
+        self.received.iter().all(|received| *received)
+    }
+
+    /// Store a received piece.
+    ///
+    /// Fails if the index is out of range or the piece has the wrong size.
+    pub fn add_piece(&mut self, piece: usize, data: &[u8]) -> Result<()> {
+        if piece >= self.num_pieces() {
+            return Err(Error::Other("Metadata piece index out of range"));
+        }
+        let start = piece * PIECE_SIZE;
+        let end = (start + PIECE_SIZE).min(self.buf.len());
+        if data.len() != end - start {
+            return Err(Error::Other("Metadata piece has wrong size"));
+        }
+        self.buf[start..end].copy_from_slice(data);
+        self.received[piece] = true;
+        Ok(())
+    }
+
+    /// Store the piece of a `Data` message, checking its `total_size`.
+    ///
+    /// Other messages are ignored.
+    pub fn add_message(&mut self, msg: &Message<'_>) -> Result<()> {
+        match *msg {
+            Message::Data {
+                piece,
+                total_size,
+                data,
+            } => {
+                if total_size != self.total_size() {
+                    return Err(Error::Other("Metadata size mismatch"));
+                }
+                self.add_piece(piece, data)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the complete metadata if all pieces were received and it
+    /// matches the info-hash.
+    ///
+    /// On a hash mismatch all pieces are marked missing again so they can be
+    /// requested from another peer.
+    pub fn verify(&mut self) -> Result<&[u8]> {
+        if !self.is_complete() {
+            return Err(Error::Other("Metadata incomplete"));
+        }
+        if Sha1::digest(&self.buf)[..] != self.info_hash {
+            self.reset();
+            return Err(Error::Other("Metadata hash mismatch"));
+        }
+        Ok(&self.buf)
+    }
+
+    /// Verify and parse the complete metadata as the `info` dictionary.
+    pub fn info<'s, 'p>(&'s mut self, parser: &'p mut Parser) -> Result<Dict<'s, 'p>> {
+        let buf = self.verify()?;
+        parser.parse(buf)
+    }
+
+    /// Mark all pieces as missing.
+    pub fn reset(&mut self) {
+        for received in &mut self.received {
+            *received = false;
+        }
+    }
+
+    /// Consume the assembler and return the raw metadata, verified or not.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+
+    /// Returns an info dictionary of exactly `len` bytes.
+    fn info(len: usize) -> Vec<u8> {
+        // `d4:name1:x7:padding` + `N:` + N bytes + `e`
+        let n = (0..len)
+            .find(|n| 21 + n.to_string().len() + n == len)
+            .unwrap();
+        let mut v = vec![];
+        let mut dict = v.add_dict();
+        dict.add("name", "x");
+        dict.add("padding", &vec![7; n][..]);
+        dict.finish();
+        assert_eq!(len, v.len());
+        v
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let parser = &mut Parser::new();
+        let data = [1u8; 100];
+        let msgs = [
+            Message::Request { piece: 1 },
+            Message::Reject { piece: 2 },
+            Message::Data {
+                piece: 3,
+                total_size: 50000,
+                data: &data,
+            },
+        ];
+        for msg in &msgs {
+            let mut buf = vec![];
+            msg.encode_message(&mut buf, 3).unwrap();
+            let (id, body, _) = extension::split_message(&buf).unwrap();
+            assert_eq!(3, id);
+            assert_eq!(*msg, Message::parse(parser, body).unwrap());
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let parser = &mut Parser::new();
+        assert_eq!(
+            Error::Invalid {
+                reason: "Unknown ut_metadata message type",
+                pos: 0,
+            },
+            Message::parse(parser, b"d8:msg_typei9e5:piecei0ee").unwrap_err()
+        );
+        assert_eq!(
+            Error::missing("piece", 0),
+            Message::parse(parser, b"d8:msg_typei0ee").unwrap_err()
+        );
+        assert_eq!(
+            Error::Invalid {
+                reason: "Extra bytes at the end",
+                pos: 25,
+            },
+            Message::parse(parser, b"d8:msg_typei0e5:piecei0eex").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn assemble() {
+        let info = info(40000);
+        let info_hash = Sha1::digest(&info).into();
+
+        let mut assembler = MetadataAssembler::new(info_hash, info.len()).unwrap();
+        assert_eq!(3, assembler.num_pieces());
+
+        for piece in [2, 0, 1].iter().copied() {
+            assert!(!assembler.is_complete());
+            let start = piece * PIECE_SIZE;
+            let end = (start + PIECE_SIZE).min(info.len());
+            let msg = Message::Data {
+                piece,
+                total_size: info.len(),
+                data: &info[start..end],
+            };
+            assembler.add_message(&msg).unwrap();
+        }
+        assert!(assembler.is_complete());
+
+        let parser = &mut Parser::new();
+        let dict = assembler.info(parser).unwrap();
+        assert_eq!(Some("x"), dict.get_str("name"));
+        assert_eq!(&info[..], dict.as_raw_bytes());
+    }
+
+    #[test]
+    fn assemble_hash_mismatch() {
+        let info = info(100);
+        let mut assembler = MetadataAssembler::new([0; 20], info.len()).unwrap();
+        assert_eq!(
+            Err(Error::Other("Metadata incomplete")),
+            assembler.verify().map(|_| ())
+        );
+
+        assembler.add_piece(0, &info).unwrap();
+        assert_eq!(
+            Err(Error::Other("Metadata hash mismatch")),
+            assembler.verify().map(|_| ())
+        );
+        assert_eq!(vec![0], assembler.missing_pieces().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn assemble_size_limit() {
+        assert_eq!(
+            Err(Error::Other("Metadata size exceeds limit")),
+            MetadataAssembler::new([0; 20], usize::MAX).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::Other("Metadata size exceeds limit")),
+            MetadataAssembler::new([0; 20], MAX_METADATA_SIZE + 1).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::Other("Metadata size exceeds limit")),
+            MetadataAssembler::with_limit([0; 20], 101, 100).map(|_| ())
+        );
+        let assembler = MetadataAssembler::with_limit([0; 20], 100, 100).unwrap();
+        assert_eq!(100, assembler.total_size());
+    }
+
+    #[test]
+    fn assemble_invalid_piece() {
+        let mut assembler = MetadataAssembler::new([0; 20], PIECE_SIZE + 1).unwrap();
+        assert_eq!(
+            Err(Error::Other("Metadata piece has wrong size")),
+            assembler.add_piece(1, &[0; 2])
+        );
+        assert_eq!(
+            Err(Error::Other("Metadata piece index out of range")),
+            assembler.add_piece(2, &[0; 1])
+        );
+        assert_eq!(
+            Err(Error::Other("Metadata size mismatch")),
+            assembler.add_message(&Message::Data {
+                piece: 1,
+                total_size: 2,
+                data: &[0],
+            })
+        );
+        assembler.add_piece(1, &[0; 1]).unwrap();
+        assert_eq!(vec![0], assembler.missing_pieces().collect::<Vec<_>>());
+    }
+}
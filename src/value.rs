@@ -0,0 +1,179 @@
+//! Owned bencode values.
+//!
+//! `Value` is a tree representation of a bencode document. It is mostly
+//! useful for testing: it always encodes to canonical bencode, and with the
+//! `arbitrary` feature it implements `arbitrary::Arbitrary` so that fuzzers
+//! can generate valid documents.
+
+use crate::decode::{Decode, Decoder};
+use crate::encode::{Encode, Encoder};
+use crate::error::{Error, Result};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// An owned bencode value.
+///
+/// # Examples
+///
+/// ```
+/// use ben::value::Value;
+/// use ben::{Encode, Parser};
+///
+/// let mut parser = Parser::new();
+/// let value = parser.parse::<Value>(b"d1:bi1e1:al1:xee").unwrap();
+/// assert_eq!(Some(&Value::Int(1)), value.get(b"b"));
+/// assert_eq!(b"d1:al1:xe1:bi1ee", &value.encode_to_vec()[..]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    /// Returns the value for given key if this is a dict.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(key.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Encode for Value {
+    fn encode<E: Encoder>(&self, enc: &mut E) {
+        match self {
+            Value::Int(n) => enc.add_int(*n),
+            Value::Bytes(bytes) => enc.add_bytes(bytes),
+            Value::List(values) => {
+                let mut list = enc.add_list();
+                for value in values {
+                    list.add(value);
+                }
+            }
+            Value::Dict(entries) => {
+                let mut dict = enc.add_dict();
+                for (key, value) in entries {
+                    dict.add(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// Decodes any value.
+///
+/// Duplicate dictionary keys keep the last value, and keys need not be
+/// sorted in the source.
+impl<'a, 'p> Decode<'a, 'p> for Value {
+    fn decode(decoder: Decoder<'a, 'p>) -> Result<Self> {
+        if let Some(list) = decoder.as_list() {
+            list.iter()
+                .map(Value::decode)
+                .collect::<Result<_>>()
+                .map(Value::List)
+        } else if let Some(dict) = decoder.as_dict() {
+            dict.iter()
+                .map(|(k, v)| Ok((k.as_raw_bytes().to_vec(), Value::decode(v)?)))
+                .collect::<Result<_>>()
+                .map(Value::Dict)
+        } else if let Some(n) = decoder.as_int() {
+            Ok(Value::Int(n))
+        } else {
+            decoder
+                .as_bytes()
+                .map(|b| Value::Bytes(b.to_vec()))
+                .ok_or(Error::TypeMismatch("Not a byte string"))
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl {
+    use super::*;
+    use arbitrary::{Arbitrary, Unstructured};
+
+    /// Nesting depth of generated values.
+    const MAX_DEPTH: usize = 16;
+
+    impl<'a> Arbitrary<'a> for Value {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+            arbitrary_value(u, MAX_DEPTH)
+        }
+    }
+
+    fn arbitrary_value(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<Value> {
+        let kind = if depth == 0 {
+            u.choose_index(2)?
+        } else {
+            u.choose_index(4)?
+        };
+        Ok(match kind {
+            0 => Value::Int(u.arbitrary()?),
+            1 => Value::Bytes(u.arbitrary()?),
+            2 => {
+                let mut list = Vec::new();
+                while !u.is_empty() && u.arbitrary()? {
+                    list.push(arbitrary_value(u, depth - 1)?);
+                }
+                Value::List(list)
+            }
+            _ => {
+                let mut dict = BTreeMap::new();
+                while !u.is_empty() && u.arbitrary()? {
+                    dict.insert(u.arbitrary()?, arbitrary_value(u, depth - 1)?);
+                }
+                Value::Dict(dict)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn decode_encode() {
+        let s = b"d1:ad1:xi-5ee1:bl3:abci0eee";
+        let value = Parser::new().parse::<Value>(s).unwrap();
+
+        let mut inner = BTreeMap::new();
+        inner.insert(b"x".to_vec(), Value::Int(-5));
+        let mut dict = BTreeMap::new();
+        dict.insert(b"a".to_vec(), Value::Dict(inner));
+        dict.insert(
+            b"b".to_vec(),
+            Value::List(vec![Value::Bytes(b"abc".to_vec()), Value::Int(0)]),
+        );
+        assert_eq!(Value::Dict(dict), value);
+        assert_eq!(&s[..], &value.encode_to_vec()[..]);
+    }
+
+    #[test]
+    fn decode_normalizes() {
+        let value = Parser::new()
+            .parse::<Value>(b"d1:bi1e1:ai2e1:bi3ee")
+            .unwrap();
+        assert_eq!(b"d1:ai2e1:bi3ee", &value.encode_to_vec()[..]);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_round_trip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut parser = Parser::new();
+        for seed in 0..200u32 {
+            let data: Vec<u8> = (0..512u32)
+                .map(|i| (i.wrapping_mul(2_654_435_761).wrapping_add(seed * 40_503) >> 13) as u8)
+                .collect();
+            let value = Value::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            let buf = value.encode_to_vec();
+            assert_eq!(value, parser.parse::<Value>(&buf).unwrap());
+        }
+    }
+}